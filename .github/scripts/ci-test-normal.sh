@@ -4,6 +4,7 @@ unset JAVA_TOOL_OPTIONS
 
 # To OpenJDK folder
 root_dir=$(dirname "$0")/../../
+tests_dir=$(realpath $(dirname "$0"))/tests
 cd $root_dir/repos/openjdk
 
 # Choose build: use slowdebug for shorter build time (32m user time for release vs. 20m user time for slowdebug)
@@ -32,12 +33,58 @@ build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHea
 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar hsqldb
 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar eclipse
 
+# Identity hashes and locks must survive moving collections, with the mark bit on the side and in the mark word
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:BiasedLockingStartupDelay=0 -Xms500M -Xmx500M $tests_dir/MarkWordPreservation.java
+MMTK_HEADER_METADATA=true build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:BiasedLockingStartupDelay=0 -Xms500M -Xmx500M $tests_dir/MarkWordPreservation.java
+
+# Classes of unreachable class loaders are unloaded
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/ClassUnloading.java
+
+# Dead interned strings are collected
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms100M -Xmx100M $tests_dir/StringInterning.java
+
+# Time the scanning loops without prefetching (the default) and with prefetch distances of 8 and 32
+MMTK_PREFETCH_DISTANCE=0 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/HeapGraph.java
+MMTK_PREFETCH_DISTANCE=8 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/HeapGraph.java
+MMTK_PREFETCH_DISTANCE=32 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/HeapGraph.java
+
+# Time instance scanning with the cached scan descriptors and with the oop maps
+MMTK_SCAN_DESCRIPTORS=true build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/HeapGraph.java
+MMTK_SCAN_DESCRIPTORS=false build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/HeapGraph.java
+
+# Load reference barrier. Nothing is forwarded between GCs, so this checks the VM side, in the interpreter, C1 and C2.
+MMTK_BARRIER=LoadReferenceBarrier build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/HeapGraph.java
+MMTK_BARRIER=LoadReferenceBarrier MMTK_HEADER_METADATA=true build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/HeapGraph.java
+MMTK_BARRIER=LoadReferenceBarrier build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xint -Xms500M -Xmx500M $tests_dir/HeapGraph.java
+MMTK_BARRIER=LoadReferenceBarrier build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:TieredStopAtLevel=1 -Xms500M -Xmx500M $tests_dir/HeapGraph.java
+MMTK_BARRIER=LoadReferenceBarrier build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar fop
+
+# Scan object arrays in small slices
+MMTK_OBJ_ARRAY_CHUNK_SIZE=16 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar pmd
+
+# Reference processing and finalization in the binding
+MMTK_NO_REFERENCE_TYPES=false build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/ReferenceProcessing.java
+MMTK_NO_REFERENCE_TYPES=false build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar fop
+
 # --- GenCopy ---
 export MMTK_PLAN=GenCopy
 
 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar antlr
 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar fop
 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar luindex
+MMTK_NO_REFERENCE_TYPES=false build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/ReferenceProcessing.java
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms100M -Xmx100M $tests_dir/StringInterning.java
+# Field-logging barrier instead of the object barrier
+MMTK_BARRIER=FieldBarrier build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar antlr
+MMTK_BARRIER=FieldBarrier build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar luindex
+# Copies into mature arrays, in the runtime, the interpreter and compiled code
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms100M -Xmx100M $tests_dir/ArrayCopyBarrier.java
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xint -Xms100M -Xmx100M $tests_dir/ArrayCopyBarrier.java
+MMTK_BARRIER=FieldBarrier build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms100M -Xmx100M $tests_dir/ArrayCopyBarrier.java
+# Time stores into mature objects with each barrier
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/StoreHeavy.java
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:TieredStopAtLevel=1 -Xms500M -Xmx500M $tests_dir/StoreHeavy.java
+MMTK_BARRIER=FieldBarrier build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/StoreHeavy.java
 # Fail non-deterministically
 # build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar hsqldb
 
@@ -76,6 +123,13 @@ build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHea
 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar fop
 #build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar jython - does not work for stock build
 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar luindex
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xms500M -Xmx500M $tests_dir/ClassUnloading.java
+# Node enqueuing, which only non-moving plans support. Edge enqueuing stays the default until it is benchmarked.
+# Compare the time per GC, and the packets and entries logged for each GC, of the two modes.
+MMTK_SCAN_MODE=edges build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xlog:gc+phases=debug -Xms500M -Xmx500M $tests_dir/HeapGraph.java
+MMTK_SCAN_MODE=nodes build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -Xlog:gc+phases=debug -Xms500M -Xmx500M $tests_dir/HeapGraph.java
+MMTK_SCAN_MODE=nodes build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar antlr
+MMTK_SCAN_MODE=nodes build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar luindex
 # build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar lusearch
 #- validation failed
 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar pmd
@@ -85,9 +139,19 @@ build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHea
 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar hsqldb
 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar eclipse
 
-# Run benchmarks with the mark bit in the header now
-unset MMTK_PLAN
-MARK_IN_HEADER=1 make CONF=linux-x86_64-normal-server-$DEBUG_LEVEL THIRD_PARTY_HEAP=$PWD/../../openjdk
+# --- MarkCompact ---
+export MMTK_PLAN=MarkCompact
+
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar antlr
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar fop
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar luindex
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar pmd
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar hsqldb
+# Locks held across a compaction, with the mark bit in the mark word
+MMTK_HEADER_METADATA=true build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:BiasedLockingStartupDelay=0 -Xms500M -Xmx500M $tests_dir/MarkWordPreservation.java
+
+# Run benchmarks with the mark bit in the header now. The same build supports both layouts.
+export MMTK_HEADER_METADATA=true
 
 export MMTK_PLAN=MarkSweep
 
@@ -106,6 +170,8 @@ build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHea
 # These benchmarks take 40s+ for slowdebug build, we may consider removing them from the CI
 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar hsqldb
 build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:MetaspaceSize=100M -Xms500M -Xmx500M -jar benchmarks/dacapo-2006-10-MR2.jar eclipse
+# Locks held across a collection, with the mark bit in the mark word
+build/linux-x86_64-normal-server-$DEBUG_LEVEL/jdk/bin/java -XX:+UseThirdPartyHeap -server -XX:BiasedLockingStartupDelay=0 -Xms500M -Xmx500M $tests_dir/MarkWordPreservation.java
 
 # --- PageProtect ---
 # Make sure this runs last in our tests unless we want to set it back to the default limit.
@@ -0,0 +1,51 @@
+import java.util.Arrays;
+
+/**
+ * Copies young objects into mature reference arrays with System.arraycopy and Arrays.copyOf,
+ * and checks that they survive the nursery GCs that follow. Each copy goes through the array
+ * copy barrier of the VM: the runtime, the interpreter or the arraycopy stubs of the compilers.
+ */
+public class ArrayCopyBarrier {
+    static final int LENGTH = 1 << 16;
+    static final int ROUNDS = 200;
+
+    static class Box {
+        final int value;
+        Box(int value) { this.value = value; }
+    }
+
+    public static void main(String[] args) {
+        Object[] old = new Object[LENGTH];
+        Box[] oldBoxes = new Box[LENGTH];
+        // Promote the arrays.
+        System.gc();
+        System.gc();
+
+        for (int round = 0; round < ROUNDS; round++) {
+            Box[] young = new Box[LENGTH];
+            for (int i = 0; i < LENGTH; i++) {
+                young[i] = new Box(round * LENGTH + i);
+            }
+            // Overlapping and disjoint copies, with and without type checks.
+            System.arraycopy(young, 0, old, 0, LENGTH);
+            System.arraycopy(old, 0, old, 1, LENGTH - 1);
+            System.arraycopy(young, 0, oldBoxes, 0, LENGTH);
+            Object[] copy = Arrays.copyOf(old, LENGTH, Object[].class);
+            System.arraycopy(copy, 0, old, 0, LENGTH);
+            young = null;
+            copy = null;
+
+            // Allocate enough to trigger nursery GCs, then check the arrays.
+            for (int i = 0; i < LENGTH; i++) {
+                new Box(i);
+            }
+            for (int i = 0; i < LENGTH; i++) {
+                int expected = round * LENGTH + Math.max(i - 1, 0);
+                if (((Box) old[i]).value != expected || oldBoxes[i].value != round * LENGTH + i) {
+                    throw new AssertionError("an element copied into a mature array was lost in round " + round);
+                }
+            }
+        }
+        System.out.println("ArrayCopyBarrier: ok");
+    }
+}
@@ -0,0 +1,80 @@
+import java.io.ByteArrayOutputStream;
+import java.io.DataOutputStream;
+import java.io.IOException;
+import java.lang.management.ClassLoadingMXBean;
+import java.lang.management.ManagementFactory;
+import java.util.ArrayList;
+import java.util.List;
+
+/**
+ * Checks that classes are unloaded once their class loader is unreachable, and that classes
+ * of reachable loaders survive collections.
+ */
+public class ClassUnloading {
+    static final int LOADERS = 1000;
+
+    static class OneClassLoader extends ClassLoader {
+        final Class<?> cls;
+
+        OneClassLoader(byte[] bytes) {
+            super(null);
+            cls = defineClass("Dummy", bytes, 0, bytes.length);
+        }
+    }
+
+    /** An empty `public class Dummy` with no members. */
+    static byte[] dummyClass() throws IOException {
+        ByteArrayOutputStream bytes = new ByteArrayOutputStream();
+        DataOutputStream out = new DataOutputStream(bytes);
+        out.writeInt(0xCAFEBABE);
+        out.writeShort(0);  // minor version
+        out.writeShort(52); // major version
+        out.writeShort(5);  // constant pool count
+        out.writeByte(7);   // #1 Class #2
+        out.writeShort(2);
+        out.writeByte(1);   // #2 Utf8
+        out.writeUTF("Dummy");
+        out.writeByte(7);   // #3 Class #4
+        out.writeShort(4);
+        out.writeByte(1);   // #4 Utf8
+        out.writeUTF("java/lang/Object");
+        out.writeShort(0x21); // ACC_PUBLIC | ACC_SUPER
+        out.writeShort(1);  // this class
+        out.writeShort(3);  // super class
+        out.writeShort(0);  // interfaces
+        out.writeShort(0);  // fields
+        out.writeShort(0);  // methods
+        out.writeShort(0);  // attributes
+        return bytes.toByteArray();
+    }
+
+    static void check(boolean condition, String message) {
+        if (!condition) {
+            throw new AssertionError(message);
+        }
+    }
+
+    public static void main(String[] args) throws IOException {
+        byte[] bytes = dummyClass();
+        ClassLoadingMXBean classLoading = ManagementFactory.getClassLoadingMXBean();
+        long unloadedBefore = classLoading.getUnloadedClassCount();
+
+        List<Class<?>> kept = new ArrayList<>();
+        for (int i = 0; i < LOADERS; i++) {
+            OneClassLoader loader = new OneClassLoader(bytes);
+            if (i % 2 == 0) {
+                kept.add(loader.cls);
+            }
+        }
+        System.gc();
+        System.gc();
+
+        long unloaded = classLoading.getUnloadedClassCount() - unloadedBefore;
+        check(unloaded >= LOADERS / 2, "only " + unloaded + " classes were unloaded");
+        for (Class<?> cls : kept) {
+            check(cls.getName().equals("Dummy"), "a live class was unloaded");
+            check(cls.getClassLoader() instanceof OneClassLoader, "a live class lost its loader");
+        }
+        System.out.println("ClassUnloading passed: " + unloaded + " classes unloaded");
+    }
+}
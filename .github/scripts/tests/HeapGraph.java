@@ -0,0 +1,74 @@
+import java.util.Random;
+
+/**
+ * Times full collections of a large heap graph shaped like the heaps of DaCapo benchmarks:
+ * small objects of a few types, reference arrays, and strings, linked mostly at random so that
+ * the GC touches them in no particular order. Run it with different MMTK_PREFETCH_DISTANCE,
+ * MMTK_SCAN_MODE or MMTK_SCAN_DESCRIPTORS values to compare the scanning loops. With
+ * -Xlog:gc+phases=debug, the binding also logs the packets and entries object scanning created
+ * in each GC, and the time spent tracing them.
+ */
+public class HeapGraph {
+    static final int NODES = 1 << 20;
+    static final int WARMUP_GCS = 3;
+    static final int TIMED_GCS = 10;
+
+    static class Node {
+        Node left;
+        Node right;
+        Object payload;
+        int value;
+    }
+
+    static class Leaf {
+        long a;
+        long b;
+    }
+
+    public static void main(String[] args) {
+        Random random = new Random(42);
+        Node[] nodes = new Node[NODES];
+        for (int i = 0; i < NODES; i++) {
+            nodes[i] = new Node();
+            nodes[i].value = i;
+            switch (i % 4) {
+                case 0: nodes[i].payload = new Leaf(); break;
+                case 1: nodes[i].payload = "node-" + i; break;
+                case 2: nodes[i].payload = new Object[4]; break;
+                default: break;
+            }
+        }
+        for (int i = 0; i < NODES; i++) {
+            nodes[i].left = nodes[random.nextInt(NODES)];
+            nodes[i].right = nodes[random.nextInt(NODES)];
+            if (nodes[i].payload instanceof Object[]) {
+                Object[] array = (Object[]) nodes[i].payload;
+                for (int j = 0; j < array.length; j++) {
+                    array[j] = nodes[random.nextInt(NODES)];
+                }
+            }
+        }
+        // Only the graph reachable from a few roots stays alive.
+        Node[] roots = new Node[16];
+        for (int i = 0; i < roots.length; i++) {
+            roots[i] = nodes[random.nextInt(NODES)];
+        }
+        nodes = null;
+
+        for (int i = 0; i < WARMUP_GCS; i++) {
+            System.gc();
+        }
+        long start = System.nanoTime();
+        for (int i = 0; i < TIMED_GCS; i++) {
+            System.gc();
+        }
+        long elapsed = System.nanoTime() - start;
+        System.out.printf(
+                "HeapGraph: %.2f ms per GC (MMTK_PREFETCH_DISTANCE=%s, MMTK_SCAN_MODE=%s, MMTK_SCAN_DESCRIPTORS=%s)%n",
+                elapsed / 1e6 / TIMED_GCS, System.getenv("MMTK_PREFETCH_DISTANCE"),
+                System.getenv("MMTK_SCAN_MODE"), System.getenv("MMTK_SCAN_DESCRIPTORS"));
+        if (roots[0].left == null) {
+            throw new AssertionError("the graph was not kept alive");
+        }
+    }
+}
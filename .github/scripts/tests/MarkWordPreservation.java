@@ -0,0 +1,125 @@
+import java.util.ArrayList;
+import java.util.List;
+
+/**
+ * Checks that the mark word of an object survives collections: identity hash codes,
+ * stack locks (displaced headers), inflated monitors and biased locks.
+ *
+ * Run with a moving plan, e.g. MMTK_PLAN=SemiSpace, or with MMTK_HEADER_METADATA=true, where
+ * the mark bit or the forwarding bits share the mark word. Use -XX:BiasedLockingStartupDelay=0
+ * so that biased locking is in effect from the start.
+ */
+public class MarkWordPreservation {
+    static final int OBJECTS = 10000;
+    static final int THREADS = 4;
+    static final int ROUNDS = 20;
+
+    static volatile Object sink;
+
+    static void allocateGarbage() {
+        for (int i = 0; i < 10000; i++) {
+            sink = new byte[128];
+        }
+    }
+
+    static void check(boolean condition, String message) {
+        if (!condition) {
+            throw new AssertionError(message);
+        }
+    }
+
+    static void checkHashes(Object[] objects, int[] hashes) {
+        for (int i = 0; i < objects.length; i++) {
+            check(System.identityHashCode(objects[i]) == hashes[i],
+                  "identity hash of object " + i + " changed");
+        }
+    }
+
+    /** Stack-locks (and biases) a chain of objects, then collects while holding all locks. */
+    static void lockChain(Object[] objects, int from, int depth) {
+        if (depth == 0) {
+            System.gc();
+            allocateGarbage();
+            for (int i = from - 1; i >= from - 8 && i >= 0; i--) {
+                check(Thread.holdsLock(objects[i]), "lost stack lock on object " + i);
+            }
+            return;
+        }
+        synchronized (objects[from]) {
+            lockChain(objects, from + 1, depth - 1);
+            check(Thread.holdsLock(objects[from]), "lost stack lock on object " + from);
+        }
+    }
+
+    static class Counter {
+        int value;
+    }
+
+    public static void main(String[] args) throws Exception {
+        final Object[] objects = new Object[OBJECTS];
+        final int[] hashes = new int[OBJECTS];
+        for (int i = 0; i < OBJECTS; i++) {
+            objects[i] = new Object();
+            // Hash every other object, so that both hashed and unhashed objects get locked.
+            if (i % 2 == 0) {
+                hashes[i] = System.identityHashCode(objects[i]);
+            }
+        }
+        for (int i = 1; i < OBJECTS; i += 2) {
+            hashes[i] = -1;
+        }
+
+        // Contended monitor: the threads below inflate it while collections happen.
+        final Counter counter = new Counter();
+        final int counterHash = System.identityHashCode(counter);
+
+        List<Thread> threads = new ArrayList<>();
+        final Throwable[] failure = new Throwable[1];
+        for (int t = 0; t < THREADS; t++) {
+            final int id = t;
+            Thread thread = new Thread(() -> {
+                try {
+                    int slice = OBJECTS / THREADS;
+                    for (int round = 0; round < ROUNDS; round++) {
+                        lockChain(objects, id * slice + (round * 8) % (slice - 8), 8);
+                        synchronized (counter) {
+                            counter.value++;
+                            if (round % 4 == 0) {
+                                counter.wait(1);
+                            }
+                            allocateGarbage();
+                            check(Thread.holdsLock(counter), "lost inflated monitor");
+                        }
+                    }
+                } catch (Throwable e) {
+                    synchronized (failure) {
+                        failure[0] = e;
+                    }
+                }
+            });
+            threads.add(thread);
+            thread.start();
+        }
+        for (Thread thread : threads) {
+            thread.join();
+        }
+        if (failure[0] != null) {
+            throw new RuntimeException(failure[0]);
+        }
+
+        System.gc();
+        for (int i = 0; i < OBJECTS; i += 2) {
+            check(System.identityHashCode(objects[i]) == hashes[i],
+                  "identity hash of object " + i + " changed");
+        }
+        // Objects that were first locked and then hashed keep the hash after another GC.
+        for (int i = 1; i < OBJECTS; i += 2) {
+            hashes[i] = System.identityHashCode(objects[i]);
+        }
+        System.gc();
+        checkHashes(objects, hashes);
+        check(System.identityHashCode(counter) == counterHash, "identity hash of monitor changed");
+        check(counter.value == THREADS * ROUNDS, "lost updates under contended monitor");
+        System.out.println("MarkWordPreservation PASSED");
+    }
+}
@@ -0,0 +1,132 @@
+import java.lang.ref.Cleaner;
+import java.lang.ref.PhantomReference;
+import java.lang.ref.ReferenceQueue;
+import java.lang.ref.SoftReference;
+import java.lang.ref.WeakReference;
+import java.util.ArrayList;
+import java.util.List;
+import java.util.concurrent.CountDownLatch;
+import java.util.concurrent.TimeUnit;
+
+/**
+ * Checks java.lang.ref semantics across collections: references to unreachable objects are
+ * cleared, soft references survive a normal collection, and references to strongly reachable
+ * objects keep pointing to them after they move. Cleared references must reach their queues
+ * through the pending list, and cleaners must run. Weak references to an object that is only
+ * reachable from an object to finalize are cleared before it is finalized, and phantom
+ * references to it are not, as the finalizer may make it reachable again.
+ *
+ * Run with MMTK_NO_REFERENCE_TYPES=false.
+ */
+public class ReferenceProcessing {
+    static final int REFERENCES = 1000;
+
+    static class Payload {
+        final int id;
+        Payload(int id) {
+            this.id = id;
+        }
+    }
+
+    /** Makes its payload reachable again when it is finalized. */
+    static class Resurrector {
+        static final List<Payload> resurrected = new ArrayList<>();
+        static final CountDownLatch finalized = new CountDownLatch(REFERENCES);
+        final Payload payload;
+        Resurrector(Payload payload) {
+            this.payload = payload;
+        }
+        @Override
+        protected void finalize() {
+            synchronized (resurrected) {
+                resurrected.add(payload);
+            }
+            finalized.countDown();
+        }
+    }
+
+    static void check(boolean condition, String message) {
+        if (!condition) {
+            throw new AssertionError(message);
+        }
+    }
+
+    /** Waits for `count` references to be enqueued on `queue`. */
+    static void drain(ReferenceQueue<Payload> queue, int count, String kind) throws InterruptedException {
+        for (int i = 0; i < count; i++) {
+            check(queue.remove(10000) != null, "only " + i + " " + kind + " references were enqueued");
+        }
+    }
+
+    public static void main(String[] args) throws InterruptedException {
+        List<Payload> strong = new ArrayList<>();
+        List<WeakReference<Payload>> live = new ArrayList<>();
+        List<WeakReference<Payload>> dead = new ArrayList<>();
+        List<SoftReference<Payload>> soft = new ArrayList<>();
+        for (int i = 0; i < REFERENCES; i++) {
+            Payload p = new Payload(i);
+            strong.add(p);
+            live.add(new WeakReference<>(p));
+            dead.add(new WeakReference<>(new Payload(i)));
+            soft.add(new SoftReference<>(new Payload(i)));
+        }
+
+        for (int round = 0; round < 3; round++) {
+            System.gc();
+            for (int i = 0; i < REFERENCES; i++) {
+                Payload p = live.get(i).get();
+                check(p == strong.get(i), "weak reference " + i + " to a live object was changed");
+                check(p.id == i, "weak reference " + i + " points to the wrong object");
+                check(dead.get(i).get() == null, "weak reference " + i + " to a dead object was not cleared");
+                Payload s = soft.get(i).get();
+                check(s != null && s.id == i, "soft reference " + i + " was not retained");
+            }
+        }
+
+        strong.clear();
+        System.gc();
+        for (int i = 0; i < REFERENCES; i++) {
+            check(live.get(i).get() == null, "weak reference " + i + " was not cleared after its referent died");
+        }
+
+        ReferenceQueue<Payload> queue = new ReferenceQueue<>();
+        List<WeakReference<Payload>> queuedWeak = new ArrayList<>();
+        List<PhantomReference<Payload>> queuedPhantom = new ArrayList<>();
+        for (int i = 0; i < REFERENCES; i++) {
+            queuedWeak.add(new WeakReference<>(new Payload(i), queue));
+            queuedPhantom.add(new PhantomReference<>(new Payload(i), queue));
+        }
+        System.gc();
+        drain(queue, 2 * REFERENCES, "weak and phantom");
+
+        Cleaner cleaner = Cleaner.create();
+        CountDownLatch cleaned = new CountDownLatch(REFERENCES);
+        for (int i = 0; i < REFERENCES; i++) {
+            cleaner.register(new Payload(i), cleaned::countDown);
+        }
+        System.gc();
+        check(cleaned.await(10, TimeUnit.SECONDS), cleaned.getCount() + " cleaning actions did not run");
+
+        ReferenceQueue<Payload> phantomQueue = new ReferenceQueue<>();
+        List<WeakReference<Payload>> finalizerReachable = new ArrayList<>();
+        List<PhantomReference<Payload>> resurrectedPhantom = new ArrayList<>();
+        for (int i = 0; i < REFERENCES; i++) {
+            Payload p = new Payload(i);
+            new Resurrector(p);
+            finalizerReachable.add(new WeakReference<>(p));
+            resurrectedPhantom.add(new PhantomReference<>(p, phantomQueue));
+        }
+        System.gc();
+        for (int i = 0; i < REFERENCES; i++) {
+            check(finalizerReachable.get(i).get() == null, "weak reference " + i + " to a finalizer-reachable object was not cleared");
+        }
+        check(Resurrector.finalized.await(10, TimeUnit.SECONDS), Resurrector.finalized.getCount() + " objects were not finalized");
+        System.gc();
+        check(phantomQueue.remove(1000) == null, "a phantom reference to a resurrected object was enqueued");
+        synchronized (Resurrector.resurrected) {
+            check(Resurrector.resurrected.size() == REFERENCES, "only " + Resurrector.resurrected.size() + " objects were resurrected");
+        }
+
+        System.out.println("ReferenceProcessing passed");
+    }
+}
@@ -0,0 +1,62 @@
+import java.util.Random;
+
+/**
+ * Times reference stores into mature objects, which all take the slow path of the object
+ * barrier of generational plans: stores into the fields of old objects, and into a large old
+ * reference array. Young objects are allocated all along, so nursery GCs process the logged
+ * objects. Run it under GenCopy to compare barriers and their slow paths.
+ */
+public class StoreHeavy {
+    static final int OLD_OBJECTS = 1 << 18;
+    static final int ROUNDS = 20;
+    static final int STORES_PER_ROUND = 1 << 22;
+
+    static class Node {
+        Object a;
+        Object b;
+    }
+
+    public static void main(String[] args) {
+        Random random = new Random(42);
+        Node[] nodes = new Node[OLD_OBJECTS];
+        Object[] array = new Object[OLD_OBJECTS];
+        for (int i = 0; i < OLD_OBJECTS; i++) {
+            nodes[i] = new Node();
+        }
+        // Promote everything.
+        System.gc();
+        System.gc();
+
+        long checksum = 0;
+        long start = System.nanoTime();
+        for (int round = 0; round < ROUNDS; round++) {
+            for (int i = 0; i < STORES_PER_ROUND; i++) {
+                Integer young = new Integer(i);
+                Node node = nodes[random.nextInt(OLD_OBJECTS)];
+                node.a = young;
+                node.b = node.a;
+                array[random.nextInt(OLD_OBJECTS)] = young;
+            }
+            for (int i = 0; i < OLD_OBJECTS; i += 4096) {
+                Object element = array[i];
+                if (element != null) {
+                    checksum += (Integer) element;
+                }
+            }
+        }
+        long elapsed = System.nanoTime() - start;
+        System.out.printf("StoreHeavy: %.2f ms per round of %d stores (MMTK_BARRIER=%s)%n",
+                elapsed / 1e6 / ROUNDS, 3 * STORES_PER_ROUND, System.getenv("MMTK_BARRIER"));
+
+        // Every young object stored into an old one must have survived the nursery GCs.
+        for (int i = 0; i < OLD_OBJECTS; i++) {
+            if (nodes[i].a != null && !(nodes[i].a instanceof Integer)) {
+                throw new AssertionError("a field of an old object points to a dead object");
+            }
+            if (array[i] != null && !(array[i] instanceof Integer)) {
+                throw new AssertionError("an element of an old array points to a dead object");
+            }
+        }
+        System.out.println("StoreHeavy: checksum " + checksum);
+    }
+}
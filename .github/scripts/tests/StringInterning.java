@@ -0,0 +1,44 @@
+import java.util.ArrayList;
+import java.util.List;
+
+/**
+ * Checks that interned strings are collected once they are unreachable, and that the live
+ * ones stay canonical after they are moved. Interning far more strings than the heap holds
+ * runs out of memory if the string table keeps its entries alive.
+ */
+public class StringInterning {
+    static final int ROUNDS = 50;
+    static final int STRINGS_PER_ROUND = 100000;
+
+    static void check(boolean condition, String message) {
+        if (!condition) {
+            throw new AssertionError(message);
+        }
+    }
+
+    static String name(int round, int i) {
+        return "interned-string-with-a-long-enough-prefix-" + round + "-" + i;
+    }
+
+    public static void main(String[] args) {
+        List<String> kept = new ArrayList<>();
+        for (int round = 0; round < ROUNDS; round++) {
+            for (int i = 0; i < STRINGS_PER_ROUND; i++) {
+                String s = name(round, i).intern();
+                if (i == 0) {
+                    kept.add(s);
+                }
+            }
+            if (round % 10 == 0) {
+                System.gc();
+            }
+        }
+        System.gc();
+
+        for (int round = 0; round < ROUNDS; round++) {
+            String s = new String(name(round, 0));
+            check(s.intern() == kept.get(round), "a live interned string is no longer canonical");
+        }
+        System.out.println("StringInterning passed: " + kept.size() + " strings kept");
+    }
+}
@@ -0,0 +1,66 @@
+//! Microbenchmarks for the object copy routine used by `VMObjectModel::copy`.
+//!
+//! Run with `cargo bench --bench object_copy`. Every benchmark sets `Bencher::bytes`, so the
+//! harness reports throughput in MB/s. `bytewise_*` is the byte-at-a-time loop the binding
+//! used before, `copy_object_*` is the current implementation.
+
+#![feature(test)]
+
+extern crate mmtk;
+extern crate test;
+
+#[path = "../src/object_copy.rs"]
+#[allow(dead_code)]
+mod object_copy;
+
+use mmtk::util::Address;
+use test::Bencher;
+
+fn bytewise(src: Address, dst: Address, bytes: usize) {
+    for i in 0..bytes {
+        unsafe { (dst + i).store((src + i).load::<u8>()) };
+    }
+}
+
+fn bench(b: &mut Bencher, bytes: usize, copy: unsafe fn(Address, Address, usize)) {
+    let words = bytes / std::mem::size_of::<usize>();
+    let src: Vec<usize> = (0..words).collect();
+    let mut dst: Vec<usize> = vec![0; words];
+    let src_addr = Address::from_ptr(src.as_ptr());
+    let dst_addr = Address::from_mut_ptr(dst.as_mut_ptr());
+    b.bytes = bytes as u64;
+    b.iter(|| {
+        unsafe { copy(test::black_box(src_addr), test::black_box(dst_addr), bytes) };
+        test::black_box(&dst);
+    });
+}
+
+unsafe fn bytewise_copy(src: Address, dst: Address, bytes: usize) {
+    bytewise(src, dst, bytes)
+}
+
+macro_rules! benches {
+    ($($size:expr => $bytewise:ident, $copy_object:ident;)*) => {
+        $(
+            #[bench]
+            fn $bytewise(b: &mut Bencher) {
+                bench(b, $size, bytewise_copy)
+            }
+
+            #[bench]
+            fn $copy_object(b: &mut Bencher) {
+                bench(b, $size, object_copy::copy_object)
+            }
+        )*
+    };
+}
+
+benches! {
+    16 => bytewise_16b, copy_object_16b;
+    48 => bytewise_48b, copy_object_48b;
+    64 => bytewise_64b, copy_object_64b;
+    256 => bytewise_256b, copy_object_256b;
+    4 << 10 => bytewise_4k, copy_object_4k;
+    64 << 10 => bytewise_64k, copy_object_64k;
+    1 << 20 => bytewise_1m, copy_object_1m;
+}
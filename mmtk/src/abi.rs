@@ -1,7 +1,7 @@
 use super::UPCALLS;
 use mmtk::util::constants::*;
 use mmtk::util::conversions;
-use mmtk::util::{Address, OpaquePointer};
+use mmtk::util::{Address, ObjectReference, OpaquePointer};
 use std::ffi::CStr;
 use std::fmt;
 use std::marker::PhantomData;
@@ -23,6 +23,9 @@ pub const fn type_equal<T, U>() -> bool {
     <T as EqualTo<U>>::VALUE
 }
 
+/// `MinObjAlignmentInBytes`. The binding assumes the default `-XX:ObjectAlignmentInBytes=8`.
+pub const MIN_OBJ_ALIGNMENT_IN_BYTES: usize = BYTES_IN_LONG;
+
 #[repr(i32)]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[allow(dead_code)]
@@ -35,6 +38,18 @@ pub enum KlassID {
     ObjArray,
 }
 
+/// `ReferenceType` in HotSpot, stored in `InstanceKlass::reference_type`.
+#[repr(u8)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReferenceType {
+    None,
+    Other,
+    Soft,
+    Weak,
+    Final,
+    Phantom,
+}
+
 #[repr(C)]
 pub struct Klass {
     vptr: OpaquePointer,
@@ -43,7 +58,7 @@ pub struct Klass {
     pub layout_helper: i32,
     pub id: KlassID,
     pub super_check_offset: u32,
-    pub name: OpaquePointer, // Symbol*
+    pub name: &'static Symbol,
     pub secondary_super_cache: &'static Klass,
     pub secondary_supers: OpaquePointer, // Array<Klass*>*
     pub primary_supers: [&'static Klass; 8],
@@ -52,7 +67,7 @@ pub struct Klass {
     pub subklass: &'static Klass,
     pub next_sibling: &'static Klass,
     pub next_link: &'static Klass,
-    pub class_loader_data: OpaquePointer, // ClassLoaderData*
+    pub class_loader_data: Address, // ClassLoaderData*
     pub modifier_flags: i32,
     pub access_flags: i32, // AccessFlags
     pub trace_id: u64,     // JFR_ONLY(traceid _trace_id;)
@@ -64,9 +79,34 @@ pub struct Klass {
 }
 
 impl Klass {
+    pub const LH_NEUTRAL_VALUE: i32 = 0;
+    pub const LH_INSTANCE_SLOW_PATH_BIT: i32 = 0x01;
+    pub const LH_LOG2_ELEMENT_SIZE_SHIFT: i32 = 0;
+    pub const LH_LOG2_ELEMENT_SIZE_MASK: i32 = BITS_IN_LONG as i32 - 1;
+    pub const LH_HEADER_SIZE_SHIFT: i32 = BITS_IN_BYTE as i32 * 2;
+    pub const LH_HEADER_SIZE_MASK: i32 = (1 << BITS_IN_BYTE) - 1;
+
     pub unsafe fn cast<'a, T>(&self) -> &'a T {
         &*(self as *const _ as usize as *const T)
     }
+
+    pub fn layout_helper_needs_slow_path(lh: i32) -> bool {
+        (lh & Self::LH_INSTANCE_SLOW_PATH_BIT) != 0
+    }
+
+    pub fn layout_helper_header_size(lh: i32) -> usize {
+        ((lh >> Self::LH_HEADER_SIZE_SHIFT) & Self::LH_HEADER_SIZE_MASK) as usize
+    }
+
+    pub fn layout_helper_log2_element_size(lh: i32) -> usize {
+        ((lh >> Self::LH_LOG2_ELEMENT_SIZE_SHIFT) & Self::LH_LOG2_ELEMENT_SIZE_MASK) as usize
+    }
+
+    /// Instance size in bytes, as encoded in a positive layout helper.
+    pub fn layout_helper_to_size_in_bytes(lh: i32) -> usize {
+        debug_assert!(lh > Self::LH_NEUTRAL_VALUE);
+        (lh as usize) & !(BYTES_IN_WORD - 1)
+    }
 }
 
 #[repr(C)]
@@ -108,7 +148,7 @@ pub struct InstanceKlass {
     // #endif
     pub idnum_allocated_count: u16,
     pub init_state: u8,
-    pub reference_type: u8,
+    reference_type: u8,
     pub this_class_index: u16,
     // #if INCLUDE_JVMTI
     pub jvmti_cached_class_field_map: OpaquePointer, // JvmtiCachedClassFieldMap*
@@ -136,6 +176,11 @@ impl InstanceKlass {
         unsafe { self.start_of_vtable().add(self.klass.vtable_len as _) }
     }
 
+    pub fn reference_type(&self) -> ReferenceType {
+        debug_assert!(self.reference_type <= ReferenceType::Phantom as u8);
+        unsafe { mem::transmute(self.reference_type) }
+    }
+
     fn nonstatic_oop_map_count(&self) -> usize {
         let oop_map_block_size = mem::size_of::<OopMapBlock>();
         let oop_map_block_size_up =
@@ -157,6 +202,12 @@ pub struct InstanceMirrorKlass {
 }
 
 impl InstanceMirrorKlass {
+    fn klass_offset() -> i32 {
+        lazy_static! {
+            pub static ref KLASS_OFFSET: i32 = unsafe { ((*UPCALLS).klass_offset_in_mirror)() };
+        }
+        *KLASS_OFFSET
+    }
     fn offset_of_static_fields() -> usize {
         lazy_static! {
             pub static ref OFFSET_OF_STATIC_FIELDS: usize =
@@ -178,6 +229,31 @@ impl InstanceMirrorKlass {
         let offset = Self::static_oop_field_count_offset();
         unsafe { oop.get_field_address(offset).load::<i32>() as _ }
     }
+    /// The klass a mirror represents. This is null for mirrors of primitive types.
+    pub fn as_klass(oop: Oop) -> Option<&'static Klass> {
+        let offset = Self::klass_offset();
+        unsafe {
+            oop.get_field_address(offset)
+                .load::<*const Klass>()
+                .as_ref()
+        }
+    }
+    /// Size of a mirror in bytes. Mirrors carry the static fields of the class they
+    /// represent, so, unlike other instances, their size is not fully described by the
+    /// layout helper (see `InstanceMirrorKlass::instance_size` in HotSpot).
+    pub fn oop_size(&self, oop: Oop) -> usize {
+        let size = Klass::layout_helper_to_size_in_bytes(self.instance_klass.klass.layout_helper);
+        match Self::as_klass(oop) {
+            Some(klass) if klass.layout_helper > Klass::LH_NEUTRAL_VALUE => {
+                let klass = unsafe { klass.cast::<InstanceKlass>() };
+                conversions::raw_align_up(
+                    size + ((klass.static_field_size as usize) << LOG_BYTES_IN_WORD),
+                    MIN_OBJ_ALIGNMENT_IN_BYTES,
+                )
+            }
+            _ => size,
+        }
+    }
 }
 
 #[repr(C)]
@@ -206,6 +282,14 @@ pub struct InstanceClassLoaderKlass {
     pub instance_klass: InstanceKlass,
 }
 
+impl InstanceClassLoaderKlass {
+    /// The `ClassLoaderData` of a class loader. This is null for a loader that has not
+    /// defined any class yet.
+    pub fn loader_data(oop: Oop) -> Address {
+        unsafe { ((*UPCALLS).loader_data)(mem::transmute(oop)) }
+    }
+}
+
 #[repr(C)]
 pub struct InstanceRefKlass {
     pub instance_klass: InstanceKlass,
@@ -230,12 +314,47 @@ impl InstanceRefKlass {
     pub fn discovered_address(oop: Oop) -> Address {
         oop.get_field_address(Self::discovered_offset())
     }
+    pub fn referent(oop: Oop) -> ObjectReference {
+        unsafe { Self::referent_address(oop).load::<ObjectReference>() }
+    }
+    pub fn set_referent(oop: Oop, referent: ObjectReference) {
+        unsafe { Self::referent_address(oop).store(referent) }
+    }
+    pub fn set_discovered(oop: Oop, discovered: ObjectReference) {
+        unsafe { Self::discovered_address(oop).store(discovered) }
+    }
+}
+
+#[repr(C)]
+pub struct Symbol {
+    length_and_refcount: u32,
+    pub identity_hash: i16,
+    body: [i8; 2],
+}
+
+impl Symbol {
+    /// Number of bytes in the symbol body. The length lives in the high half word.
+    pub fn length(&self) -> usize {
+        (self.length_and_refcount >> 16) as usize
+    }
+
+    /// The symbol body in modified UTF-8. It is not null-terminated.
+    pub fn as_bytes(&self) -> &[i8] {
+        unsafe { slice::from_raw_parts(self.body.as_ptr(), self.length()) }
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = unsafe { &*(self.as_bytes() as *const [i8] as *const [u8]) };
+        write!(f, "{}", String::from_utf8_lossy(bytes))
+    }
 }
 
 #[repr(C)]
 pub struct OopDesc {
     pub mark: usize,
-    pub klass: &'static Klass,
+    klass: &'static Klass,
 }
 
 impl fmt::Debug for OopDesc {
@@ -250,6 +369,11 @@ impl fmt::Debug for OopDesc {
 pub type Oop = &'static OopDesc;
 
 impl OopDesc {
+    #[inline(always)]
+    pub fn klass(&self) -> &'static Klass {
+        self.klass
+    }
+
     pub unsafe fn as_array_oop<T>(&self) -> ArrayOop<T> {
         &*(self as *const OopDesc as *const ArrayOopDesc<T>)
     }
@@ -257,6 +381,32 @@ impl OopDesc {
     pub fn get_field_address(&self, offset: i32) -> Address {
         Address::from_ref(self) + offset as isize
     }
+
+    /// Size of the object in bytes, computed the same way as `oopDesc::size_given_klass`.
+    #[inline]
+    pub fn size(&self) -> usize {
+        let klass = self.klass();
+        let lh = klass.layout_helper;
+        if lh > Klass::LH_NEUTRAL_VALUE {
+            if !Klass::layout_helper_needs_slow_path(lh) {
+                Klass::layout_helper_to_size_in_bytes(lh)
+            } else if klass.id == KlassID::InstanceMirror {
+                let oop: Oop = unsafe { mem::transmute(self) };
+                unsafe { klass.cast::<InstanceMirrorKlass>() }.oop_size(oop)
+            } else {
+                // Finalizable, abstract and very large classes have the slow path bit set only
+                // to disable fast path allocation. Their size is still what the layout helper says.
+                Klass::layout_helper_to_size_in_bytes(lh)
+            }
+        } else if lh < Klass::LH_NEUTRAL_VALUE {
+            let length = unsafe { self.as_array_oop::<u8>() }.length() as usize;
+            let size_in_bytes = (length << Klass::layout_helper_log2_element_size(lh))
+                + Klass::layout_helper_header_size(lh);
+            conversions::raw_align_up(size_in_bytes, MIN_OBJ_ALIGNMENT_IN_BYTES)
+        } else {
+            unsafe { ((*UPCALLS).get_object_size)(mem::transmute(self)) }
+        }
+    }
 }
 
 #[repr(C)]
@@ -271,7 +421,10 @@ impl<T> ArrayOopDesc<T> {
         let typesize_in_bytes =
             conversions::raw_align_up(Self::LENGTH_OFFSET + BYTES_IN_INT, BYTES_IN_LONG);
         if Self::ELEMENT_TYPE_SHOULD_BE_ALIGNED {
-            conversions::raw_align_up(typesize_in_bytes / BYTES_IN_WORD, BYTES_IN_LONG)
+            conversions::raw_align_up(
+                typesize_in_bytes / BYTES_IN_WORD,
+                BYTES_IN_LONG / BYTES_IN_WORD,
+            )
         } else {
             typesize_in_bytes / BYTES_IN_WORD
         }
@@ -305,6 +458,8 @@ pub fn validate_memory_layouts() {
             ^ mem::size_of::<InstanceClassLoaderKlass>()
             ^ mem::size_of::<TypeArrayKlass>()
             ^ mem::size_of::<ObjArrayKlass>()
+            ^ mem::size_of::<Symbol>()
+            ^ mem::size_of::<OopDesc>()
     };
     assert_eq!(vm_checksum, binding_checksum);
 }
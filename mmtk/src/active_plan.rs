@@ -1,6 +1,6 @@
 use super::UPCALLS;
+use crate::singleton;
 use crate::OpenJDK;
-use crate::SINGLETON;
 use mmtk::util::opaque_pointer::*;
 use mmtk::vm::ActivePlan;
 use mmtk::Mutator;
@@ -9,18 +9,18 @@ use std::sync::Mutex;
 
 pub struct VMActivePlan {}
 
-impl ActivePlan<OpenJDK> for VMActivePlan {
-    fn global() -> &'static dyn Plan<VM = OpenJDK> {
-        SINGLETON.get_plan()
+impl<const H: bool> ActivePlan<OpenJDK<H>> for VMActivePlan {
+    fn global() -> &'static dyn Plan<VM = OpenJDK<H>> {
+        singleton::<H>().get_plan()
     }
 
     fn is_mutator(tls: VMThread) -> bool {
         unsafe { ((*UPCALLS).is_mutator)(tls) }
     }
 
-    fn mutator(tls: VMMutatorThread) -> &'static mut Mutator<OpenJDK> {
+    fn mutator(tls: VMMutatorThread) -> &'static mut Mutator<OpenJDK<H>> {
         unsafe {
-            let m = ((*UPCALLS).get_mmtk_mutator)(tls);
+            let m = ((*UPCALLS).get_mmtk_mutator)(tls) as *mut Mutator<OpenJDK<H>>;
             &mut *m
         }
     }
@@ -31,10 +31,10 @@ impl ActivePlan<OpenJDK> for VMActivePlan {
         }
     }
 
-    fn get_next_mutator() -> Option<&'static mut Mutator<OpenJDK>> {
+    fn get_next_mutator() -> Option<&'static mut Mutator<OpenJDK<H>>> {
         let _guard = MUTATOR_ITERATOR_LOCK.lock().unwrap();
         unsafe {
-            let m = ((*UPCALLS).get_next_mutator)();
+            let m = ((*UPCALLS).get_next_mutator)() as *mut Mutator<OpenJDK<H>>;
             if m.is_null() {
                 None
             } else {
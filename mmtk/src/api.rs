@@ -1,10 +1,10 @@
+use crate::barriers::{Barrier, BarrierDescriptor};
+use crate::NewBuffer;
 use crate::OpenJDK;
 use crate::OpenJDK_Upcalls;
-use crate::SINGLETON;
 use crate::UPCALLS;
-use libc::c_char;
+use libc::{c_char, c_void};
 use mmtk::memory_manager;
-use mmtk::plan::BarrierSelector;
 use mmtk::scheduler::GCWorker;
 use mmtk::util::alloc::AllocatorSelector;
 use mmtk::util::opaque_pointer::*;
@@ -13,102 +13,175 @@ use mmtk::AllocationSemantics;
 use mmtk::Mutator;
 use mmtk::MutatorContext;
 use mmtk::MMTK;
-use std::ffi::{CStr, CString};
-use std::lazy::SyncLazy;
-
-// Supported barriers:
-static NO_BARRIER: SyncLazy<CString> = SyncLazy::new(|| CString::new("NoBarrier").unwrap());
-static OBJECT_BARRIER: SyncLazy<CString> = SyncLazy::new(|| CString::new("ObjectBarrier").unwrap());
+use std::ffi::CStr;
 
+/// The active barrier, and how the VM emits it. Only valid after `openjdk_gc_init`.
 #[no_mangle]
-pub extern "C" fn mmtk_active_barrier() -> *const c_char {
-    match SINGLETON.get_plan().constraints().barrier {
-        BarrierSelector::NoBarrier => NO_BARRIER.as_ptr(),
-        BarrierSelector::ObjectBarrier => OBJECT_BARRIER.as_ptr(),
-        // In case we have more barriers in mmtk-core.
-        #[allow(unreachable_patterns)]
-        _ => unimplemented!(),
-    }
+pub extern "C" fn mmtk_barrier_descriptor() -> *const BarrierDescriptor {
+    crate::barriers::descriptor()
 }
 
 /// # Safety
 /// Caller needs to make sure the ptr is a valid vector pointer.
 #[no_mangle]
 pub unsafe extern "C" fn release_buffer(ptr: *mut Address, length: usize, capacity: usize) {
-    let _vec = Vec::<Address>::from_raw_parts(ptr, length, capacity);
+    let vec = Vec::<Address>::from_raw_parts(ptr, length, capacity);
+    crate::edge_buffer::recycle(vec);
+}
+
+/// Edge buffers reused and allocated since the last call. The VM calls this once per GC.
+#[no_mangle]
+pub extern "C" fn mmtk_take_edge_buffer_stats() -> crate::edge_buffer::EdgeBufferStats {
+    crate::edge_buffer::take_stats()
+}
+
+/// Object scanning packets and trace time since the last call. The VM calls this once per GC.
+#[no_mangle]
+pub extern "C" fn mmtk_take_scan_stats() -> crate::object_scanning::ScanStats {
+    crate::object_scanning::take_stats()
+}
+
+/// Drop the cached scan descriptors of the klasses. The VM calls this after unloading classes,
+/// and before a GC if classes may have been redefined.
+#[no_mangle]
+pub extern "C" fn mmtk_invalidate_scan_descriptors() {
+    crate::scan_descriptor::invalidate()
 }
 
 #[no_mangle]
 pub extern "C" fn openjdk_gc_init(calls: *const OpenJDK_Upcalls, heap_size: usize) {
     unsafe { UPCALLS = calls };
+    let options = crate::options::OPTIONS.lock().unwrap();
+    crate::vm_metadata::initialize(&options);
     crate::abi::validate_memory_layouts();
-    // MMTk should not be used before gc_init, and gc_init is single threaded. It is fine we get a mutable reference from the singleton.
-    #[allow(clippy::cast_ref_to_mut)]
-    let singleton_mut =
-        unsafe { &mut *(&*SINGLETON as *const MMTK<OpenJDK> as *mut MMTK<OpenJDK>) };
-    memory_manager::gc_init(singleton_mut, heap_size);
+    crate::prefetch::initialize(&options);
+    with_singleton!(|VM, singleton| {
+        // MMTk should not be used before gc_init, and gc_init is single threaded. It is fine we get a mutable reference from the singleton.
+        #[allow(clippy::cast_ref_to_mut)]
+        let singleton_mut = unsafe { &mut *(singleton as *const MMTK<VM> as *mut MMTK<VM>) };
+        // The binding finalizes objects itself, in order with the references (see
+        // `reference_processor`).
+        let finalization_enabled = !singleton.options.no_finalizer;
+        assert!(memory_manager::process(singleton, "no_finalizer", "true"));
+        memory_manager::gc_init(singleton_mut, heap_size);
+        crate::object_scanning::initialize(
+            &options,
+            singleton.get_plan().constraints().moves_objects,
+        );
+        crate::barriers::initialize(
+            &options,
+            singleton.options.plan,
+            singleton.get_plan().constraints().barrier,
+        );
+        crate::field_barrier::initialize();
+        crate::reference_processor::initialize(
+            !singleton.options.no_reference_types,
+            finalization_enabled,
+        );
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn start_control_collector(tls: VMWorkerThread) {
-    memory_manager::start_control_collector(&SINGLETON, tls);
+    with_singleton!(|_VM, singleton| memory_manager::start_control_collector(singleton, tls))
 }
 
 #[no_mangle]
-pub extern "C" fn bind_mutator(tls: VMMutatorThread) -> *mut Mutator<OpenJDK> {
-    Box::into_raw(memory_manager::bind_mutator(&SINGLETON, tls))
+pub extern "C" fn bind_mutator(tls: VMMutatorThread) -> *mut c_void {
+    with_singleton!(|_VM, singleton| {
+        Box::into_raw(memory_manager::bind_mutator(singleton, tls)) as *mut c_void
+    })
 }
 
+/// The entry points that take a mutator, for the metadata layout it was bound with. The VM keeps
+/// a pointer to them in each mutator (see `mmtk_mutator_functions`), so that the allocation
+/// paths call straight into the instance of the layout.
+#[repr(C)]
+pub struct MutatorFunctions {
+    pub destroy: extern "C" fn(*mut c_void),
+    pub flush: extern "C" fn(*mut c_void),
+    pub alloc: extern "C" fn(*mut c_void, usize, usize, isize, AllocationSemantics) -> Address,
+    pub post_alloc: extern "C" fn(*mut c_void, ObjectReference, usize, AllocationSemantics),
+    pub record_modified_node: extern "C" fn(*mut c_void, ObjectReference),
+}
+
+macro_rules! mutator_functions {
+    ($h:literal) => {
+        MutatorFunctions {
+            destroy: destroy_mutator::<$h>,
+            flush: flush_mutator::<$h>,
+            alloc: alloc::<$h>,
+            post_alloc: post_alloc::<$h>,
+            record_modified_node: record_modified_node::<$h>,
+        }
+    };
+}
+
+static MUTATOR_FUNCTIONS_HEADER: MutatorFunctions = mutator_functions!(true);
+static MUTATOR_FUNCTIONS_SIDE: MutatorFunctions = mutator_functions!(false);
+
+/// The entry points for the mutators of the selected layout. Only valid after `openjdk_gc_init`.
 #[no_mangle]
+pub extern "C" fn mmtk_mutator_functions() -> *const MutatorFunctions {
+    if crate::vm_metadata::header_metadata() {
+        &MUTATOR_FUNCTIONS_HEADER
+    } else {
+        &MUTATOR_FUNCTIONS_SIDE
+    }
+}
+
 // It is fine we turn the pointer back to box, as we turned a boxed value to the raw pointer in bind_mutator()
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
-pub extern "C" fn destroy_mutator(mutator: *mut Mutator<OpenJDK>) {
-    memory_manager::destroy_mutator(unsafe { Box::from_raw(mutator) })
+extern "C" fn destroy_mutator<const H: bool>(mutator: *mut c_void) {
+    memory_manager::destroy_mutator(unsafe { Box::from_raw(mutator as *mut Mutator<OpenJDK<H>>) })
 }
 
-#[no_mangle]
 // We trust the mutator pointer is valid.
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
-pub extern "C" fn flush_mutator(mutator: *mut Mutator<OpenJDK>) {
-    memory_manager::flush_mutator(unsafe { &mut *mutator })
+extern "C" fn flush_mutator<const H: bool>(mutator: *mut c_void) {
+    memory_manager::flush_mutator(unsafe { &mut *(mutator as *mut Mutator<OpenJDK<H>>) })
 }
 
-#[no_mangle]
 // We trust the mutator pointer is valid.
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
-pub extern "C" fn alloc(
-    mutator: *mut Mutator<OpenJDK>,
+extern "C" fn alloc<const H: bool>(
+    mutator: *mut c_void,
     size: usize,
     align: usize,
     offset: isize,
     allocator: AllocationSemantics,
 ) -> Address {
-    memory_manager::alloc::<OpenJDK>(unsafe { &mut *mutator }, size, align, offset, allocator)
+    let mutator = unsafe { &mut *(mutator as *mut Mutator<OpenJDK<H>>) };
+    memory_manager::alloc::<OpenJDK<H>>(mutator, size, align, offset, allocator)
 }
 
 #[no_mangle]
 pub extern "C" fn get_allocator_mapping(allocator: AllocationSemantics) -> AllocatorSelector {
-    memory_manager::get_allocator_mapping(&SINGLETON, allocator)
+    with_singleton!(|_VM, singleton| {
+        memory_manager::get_allocator_mapping(singleton, allocator)
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn get_max_non_los_default_alloc_bytes() -> usize {
-    SINGLETON
-        .get_plan()
-        .constraints()
-        .max_non_los_default_alloc_bytes
+    with_singleton!(|_VM, singleton| {
+        singleton
+            .get_plan()
+            .constraints()
+            .max_non_los_default_alloc_bytes
+    })
 }
 
-#[no_mangle]
 // We trust the mutator pointer is valid.
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
-pub extern "C" fn post_alloc(
-    mutator: *mut Mutator<OpenJDK>,
+extern "C" fn post_alloc<const H: bool>(
+    mutator: *mut c_void,
     refer: ObjectReference,
     bytes: usize,
     allocator: AllocationSemantics,
 ) {
-    memory_manager::post_alloc::<OpenJDK>(unsafe { &mut *mutator }, refer, bytes, allocator)
+    let mutator = unsafe { &mut *(mutator as *mut Mutator<OpenJDK<H>>) };
+    memory_manager::post_alloc::<OpenJDK<H>>(mutator, refer, bytes, allocator)
 }
 
 #[no_mangle]
@@ -119,39 +192,44 @@ pub extern "C" fn will_never_move(object: ObjectReference) -> bool {
 #[no_mangle]
 // We trust the worker pointer is valid.
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
-pub extern "C" fn start_worker(tls: VMWorkerThread, worker: *mut GCWorker<OpenJDK>) {
-    memory_manager::start_worker::<OpenJDK>(tls, unsafe { worker.as_mut().unwrap() }, &SINGLETON)
+pub extern "C" fn start_worker(tls: VMWorkerThread, worker: *mut c_void) {
+    with_singleton!(|VM, singleton| {
+        let worker = unsafe { (worker as *mut GCWorker<VM>).as_mut().unwrap() };
+        memory_manager::start_worker::<VM>(tls, worker, singleton)
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn enable_collection(tls: VMThread) {
-    memory_manager::enable_collection(&SINGLETON, tls)
+    with_singleton!(|_VM, singleton| memory_manager::enable_collection(singleton, tls))
 }
 
 #[no_mangle]
 pub extern "C" fn used_bytes() -> usize {
-    memory_manager::used_bytes(&SINGLETON)
+    with_singleton!(|_VM, singleton| memory_manager::used_bytes(singleton))
 }
 
 #[no_mangle]
 pub extern "C" fn free_bytes() -> usize {
-    memory_manager::free_bytes(&SINGLETON)
+    with_singleton!(|_VM, singleton| memory_manager::free_bytes(singleton))
 }
 
 #[no_mangle]
 pub extern "C" fn total_bytes() -> usize {
-    memory_manager::total_bytes(&SINGLETON)
+    with_singleton!(|_VM, singleton| memory_manager::total_bytes(singleton))
 }
 
 #[no_mangle]
 #[cfg(feature = "sanity")]
 pub extern "C" fn scan_region() {
-    memory_manager::scan_region(&SINGLETON)
+    with_singleton!(|_VM, singleton| memory_manager::scan_region(singleton))
 }
 
 #[no_mangle]
 pub extern "C" fn handle_user_collection_request(tls: VMMutatorThread) {
-    memory_manager::handle_user_collection_request::<OpenJDK>(&SINGLETON, tls);
+    with_singleton!(|VM, singleton| {
+        memory_manager::handle_user_collection_request::<VM>(singleton, tls)
+    })
 }
 
 #[no_mangle]
@@ -166,22 +244,28 @@ pub extern "C" fn is_mapped_address(addr: Address) -> bool {
 
 #[no_mangle]
 pub extern "C" fn modify_check(object: ObjectReference) {
-    memory_manager::modify_check(&SINGLETON, object)
+    with_singleton!(|_VM, singleton| memory_manager::modify_check(singleton, object))
 }
 
 #[no_mangle]
 pub extern "C" fn add_weak_candidate(reff: ObjectReference, referent: ObjectReference) {
-    memory_manager::add_weak_candidate(&SINGLETON, reff, referent)
+    with_singleton!(|_VM, singleton| {
+        memory_manager::add_weak_candidate(singleton, reff, referent)
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn add_soft_candidate(reff: ObjectReference, referent: ObjectReference) {
-    memory_manager::add_soft_candidate(&SINGLETON, reff, referent)
+    with_singleton!(|_VM, singleton| {
+        memory_manager::add_soft_candidate(singleton, reff, referent)
+    })
 }
 
 #[no_mangle]
 pub extern "C" fn add_phantom_candidate(reff: ObjectReference, referent: ObjectReference) {
-    memory_manager::add_phantom_candidate(&SINGLETON, reff, referent)
+    with_singleton!(|_VM, singleton| {
+        memory_manager::add_phantom_candidate(singleton, reff, referent)
+    })
 }
 
 // The harness_begin()/end() functions are different than other API functions in terms of the thread state.
@@ -193,14 +277,16 @@ pub extern "C" fn add_phantom_candidate(reff: ObjectReference, referent: ObjectR
 pub extern "C" fn harness_begin(_id: usize) {
     let state = unsafe { ((*UPCALLS).enter_vm)() };
     // Pass null as tls, OpenJDK binding does not rely on the tls value to block the current thread and do a GC
-    memory_manager::harness_begin(&SINGLETON, VMMutatorThread(VMThread::UNINITIALIZED));
+    with_singleton!(|_VM, singleton| {
+        memory_manager::harness_begin(singleton, VMMutatorThread(VMThread::UNINITIALIZED))
+    });
     unsafe { ((*UPCALLS).leave_vm)(state) };
 }
 
 #[no_mangle]
 pub extern "C" fn harness_end(_id: usize) {
     let state = unsafe { ((*UPCALLS).enter_vm)() };
-    memory_manager::harness_end(&SINGLETON);
+    with_singleton!(|_VM, singleton| memory_manager::harness_end(singleton));
     unsafe { ((*UPCALLS).leave_vm)(state) };
 }
 
@@ -210,11 +296,21 @@ pub extern "C" fn harness_end(_id: usize) {
 pub extern "C" fn process(name: *const c_char, value: *const c_char) -> bool {
     let name_str: &CStr = unsafe { CStr::from_ptr(name) };
     let value_str: &CStr = unsafe { CStr::from_ptr(value) };
-    memory_manager::process(
-        &SINGLETON,
-        name_str.to_str().unwrap(),
-        value_str.to_str().unwrap(),
-    )
+    // The options of the binding first, then the ones of mmtk-core.
+    if let Some(valid) = crate::options::OPTIONS
+        .lock()
+        .unwrap()
+        .process(name_str.to_str().unwrap(), value_str.to_str().unwrap())
+    {
+        return valid;
+    }
+    with_singleton!(|_VM, singleton| {
+        memory_manager::process(
+            singleton,
+            name_str.to_str().unwrap(),
+            value_str.to_str().unwrap(),
+        )
+    })
 }
 
 #[no_mangle]
@@ -229,7 +325,7 @@ pub extern "C" fn last_heap_address() -> Address {
 
 #[no_mangle]
 pub extern "C" fn openjdk_max_capacity() -> usize {
-    memory_manager::total_bytes(&SINGLETON)
+    with_singleton!(|_VM, singleton| memory_manager::total_bytes(singleton))
 }
 
 #[no_mangle]
@@ -237,23 +333,119 @@ pub extern "C" fn executable() -> bool {
     true
 }
 
+// We trust the mutator pointer is valid.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+extern "C" fn record_modified_node<const H: bool>(mutator: *mut c_void, obj: ObjectReference) {
+    let mutator = unsafe { &mut *(mutator as *mut Mutator<OpenJDK<H>>) };
+    mutator.record_modified_node(obj)
+}
+
+/// The capacity of the buffers the VM logs modified objects into.
+const MODIFIED_NODES_BUFFER_CAPACITY: usize = 4096;
+
+/// The slow path of the object barrier, for a batch of objects. The VM logs the objects of each
+/// thread in a buffer, and hands it over when it is full, or when the mutators are stopped for a
+/// GC. Like with the edge buffers of root scanning, it gets an empty buffer back (a null `buf`
+/// only asks for one), and gives the last one back with `release_buffer`. The VM clears the
+/// unlogged bit of each object it logs (see `object_barrier`).
+#[no_mangle]
+pub extern "C" fn mmtk_record_modified_nodes(
+    _mutator: *mut c_void,
+    buf: *mut Address,
+    length: usize,
+    capacity: usize,
+) -> NewBuffer {
+    if !buf.is_null() {
+        let objects = unsafe { Vec::<Address>::from_raw_parts(buf, length, capacity) };
+        crate::object_barrier::add_logged_nodes(objects);
+    }
+    let (ptr, _, capacity) =
+        crate::edge_buffer::take(MODIFIED_NODES_BUFFER_CAPACITY).into_raw_parts();
+    NewBuffer { ptr, capacity }
+}
+
+/// The capacity of the buffers the VM logs modified slots into.
+const MODIFIED_EDGES_BUFFER_CAPACITY: usize = 4096;
+
+/// The slots logged by the field barrier, or by the object barrier for array copies, in a batch.
+/// The buffers are exchanged like with `mmtk_record_modified_nodes`. The VM sets the log bit of
+/// each slot it logs (see `field_barrier`).
+#[no_mangle]
+pub extern "C" fn mmtk_record_modified_edges(
+    _mutator: *mut c_void,
+    buf: *mut Address,
+    length: usize,
+    capacity: usize,
+) -> NewBuffer {
+    if !buf.is_null() {
+        let slots = unsafe { Vec::<Address>::from_raw_parts(buf, length, capacity) };
+        crate::field_barrier::add_logged_slots(slots);
+    }
+    let (ptr, _, capacity) =
+        crate::edge_buffer::take(MODIFIED_EDGES_BUFFER_CAPACITY).into_raw_parts();
+    NewBuffer { ptr, capacity }
+}
+
+/// Called before the VM copies `count` elements into the reference array `dst`, from the slot
+/// `dst_slots` on. `src` and `dst` are null when the VM only knows the slots. None of our
+/// barriers needs to see the overwritten elements, so there is nothing to do yet.
 #[no_mangle]
-pub extern "C" fn record_modified_node(
-    mutator: &'static mut Mutator<OpenJDK>,
-    obj: ObjectReference,
+pub extern "C" fn array_copy_pre(
+    _mutator: *mut c_void,
+    _src: ObjectReference,
+    _dst: ObjectReference,
+    _dst_slots: Address,
+    _count: usize,
+) {
+}
+
+/// Called after the VM copied `count` elements into the reference array `dst`, from the slot
+/// `dst_slots` on. `src` and `dst` are null when the VM only knows the slots.
+///
+/// If `dst` is mature, the object barrier logs it once, whatever the number of elements, and
+/// the field barrier logs the copied slots. The arraycopy stubs of the VM only pass the slots,
+/// so without `dst` both barriers log the copied slots, mature or not. The log bits of the
+/// slots keep each of them from being logged more than once between two GCs.
+#[no_mangle]
+pub extern "C" fn array_copy_post(
+    _mutator: *mut c_void,
+    _src: ObjectReference,
+    dst: ObjectReference,
+    dst_slots: Address,
+    count: usize,
 ) {
-    mutator.record_modified_node(obj);
+    match crate::barriers::active() {
+        Barrier::ObjectBarrier if !dst.is_null() => {
+            // An array is logged at most once between two GCs, so the buffer is rarely needed.
+            if crate::object_barrier::log_object(dst.to_address()) {
+                crate::object_barrier::add_logged_nodes(vec![dst.to_address()]);
+            }
+        }
+        Barrier::ObjectBarrier | Barrier::FieldBarrier => {
+            if dst.is_null() || crate::object_barrier::is_unlogged(dst.to_address()) {
+                crate::field_barrier::log_copied_slots(dst_slots, count);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The slow path of the load reference barrier: the current copy of `object`, which may have
+/// been forwarded.
+#[no_mangle]
+pub extern "C" fn mmtk_load_reference_slow(object: ObjectReference) -> ObjectReference {
+    with_singleton!(|VM, _singleton| crate::load_reference_barrier::resolve::<VM>(object))
 }
 
 // finalization
 #[no_mangle]
 pub extern "C" fn add_finalizer(object: ObjectReference) {
-    memory_manager::add_finalizer(&SINGLETON, object);
+    crate::reference_processor::add_finalizer(object)
 }
 
 #[no_mangle]
 pub extern "C" fn get_finalized_object() -> ObjectReference {
-    match memory_manager::get_finalized_object(&SINGLETON) {
+    match crate::reference_processor::get_finalized_object() {
         Some(obj) => obj,
         None => unsafe { Address::ZERO.to_object_reference() },
     }
@@ -0,0 +1,153 @@
+//! The barrier the VM emits, selected with the `barrier` option.
+//!
+//! By default, the VM emits the write barrier of the plan. A binding barrier may replace it:
+//! the field barrier (see `field_barrier`) refines the object barrier, and the load reference
+//! barrier (see `load_reference_barrier`) is for plans that move objects while the mutators run.
+//!
+//! The VM learns which barrier is active, and how to emit its fast paths, from the
+//! `BarrierDescriptor` (see `mmtk_barrier_descriptor` in `api`).
+
+use crate::options::BindingOptions;
+use crate::{vm_metadata, NewBuffer};
+use libc::c_void;
+use mmtk::plan::BarrierSelector;
+use mmtk::util::constants::{BITS_IN_BYTE, LOG_BITS_IN_BYTE};
+use mmtk::util::options::PlanSelector;
+use mmtk::util::{Address, ObjectReference};
+use std::lazy::SyncLazy;
+use std::str::FromStr;
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Barrier {
+    NoBarrier,
+    ObjectBarrier,
+    FieldBarrier,
+    LoadReferenceBarrier,
+}
+
+/// The `barrier` option. `ObjectBarrier` and `NoBarrier` select the write barrier of the plan,
+/// like the default.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BarrierOption {
+    Plan,
+    FieldBarrier,
+    LoadReferenceBarrier,
+}
+
+impl FromStr for BarrierOption {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ObjectBarrier" | "NoBarrier" => Ok(BarrierOption::Plan),
+            "FieldBarrier" => Ok(BarrierOption::FieldBarrier),
+            "LoadReferenceBarrier" => Ok(BarrierOption::LoadReferenceBarrier),
+            _ => Err(()),
+        }
+    }
+}
+
+static mut ACTIVE_BARRIER: Barrier = Barrier::NoBarrier;
+
+/// Read the `barrier` option. This is called from `openjdk_gc_init`, once the plan is created.
+pub fn initialize(options: &BindingOptions, plan: PlanSelector, plan_barrier: BarrierSelector) {
+    let plan_default = match plan_barrier {
+        BarrierSelector::NoBarrier => Barrier::NoBarrier,
+        BarrierSelector::ObjectBarrier => Barrier::ObjectBarrier,
+        // In case we have more barriers in mmtk-core.
+        #[allow(unreachable_patterns)]
+        _ => unimplemented!(),
+    };
+    let barrier = match options.barrier {
+        BarrierOption::Plan => plan_default,
+        BarrierOption::FieldBarrier => Barrier::FieldBarrier,
+        BarrierOption::LoadReferenceBarrier => Barrier::LoadReferenceBarrier,
+    };
+    assert!(
+        barrier != Barrier::FieldBarrier || plan_default == Barrier::ObjectBarrier,
+        "MMTK_BARRIER=FieldBarrier is only supported by plans that use the object barrier"
+    );
+    // The load reference barrier replaces the write barrier, so the plan must not need one. It
+    // also reads the forwarding bits of every object it loads, so they must not be shared with
+    // a mark bit, as they are with the header metadata of the marking plans.
+    assert!(
+        barrier != Barrier::LoadReferenceBarrier || matches!(plan, PlanSelector::SemiSpace),
+        "MMTK_BARRIER=LoadReferenceBarrier is only supported by SemiSpace"
+    );
+    unsafe { ACTIVE_BARRIER = barrier };
+}
+
+/// The active barrier. Only valid after `openjdk_gc_init`.
+#[inline(always)]
+pub fn active() -> Barrier {
+    unsafe { ACTIVE_BARRIER }
+}
+
+/// Everything the VM needs to emit the active barrier in the interpreter, C1 and C2. The fast
+/// paths take the geometry of the metadata from here rather than hard-coding it.
+#[repr(C)]
+pub struct BarrierDescriptor {
+    pub kind: Barrier,
+    /// Whether the mark bit and the forwarding bits are in the mark word, as selected by the
+    /// `header_metadata` option.
+    pub header_metadata: bool,
+    /// The log bit of an object is bit `(object >> log_bit_shift) & log_bit_mask` of the byte at
+    /// `log_bit_base + (object >> log_bit_byte_shift)`. The object and field barriers take the
+    /// slow path when it is set.
+    pub log_bit_base: Address,
+    pub log_bit_byte_shift: usize,
+    pub log_bit_shift: usize,
+    pub log_bit_mask: usize,
+    /// The log bit of a slot is bit `(slot >> slot_log_shift) & log_bit_mask` of the byte at
+    /// `slot_log_base + (slot >> slot_log_byte_shift)`. The VM sets it when it logs the slot, and
+    /// does not log slots whose bit is already set. See `field_barrier`.
+    pub slot_log_base: Address,
+    pub slot_log_byte_shift: usize,
+    pub slot_log_shift: usize,
+    /// An object is forwarded or being forwarded if its mark word has any of the bits of
+    /// `forwarding_bits_mask`, which starts at bit `forwarding_bits_shift`, in both layouts.
+    /// The load reference barrier takes the slow path then.
+    pub forwarding_bits_shift: usize,
+    pub forwarding_bits_mask: usize,
+    pub record_modified_nodes: extern "C" fn(*mut c_void, *mut Address, usize, usize) -> NewBuffer,
+    pub record_modified_edges: extern "C" fn(*mut c_void, *mut Address, usize, usize) -> NewBuffer,
+    pub array_copy_pre:
+        extern "C" fn(*mut c_void, ObjectReference, ObjectReference, Address, usize),
+    pub array_copy_post:
+        extern "C" fn(*mut c_void, ObjectReference, ObjectReference, Address, usize),
+    pub load_reference_slow: extern "C" fn(ObjectReference) -> ObjectReference,
+}
+
+static DESCRIPTOR: SyncLazy<BarrierDescriptor> = SyncLazy::new(|| {
+    let log_bit = vm_metadata::LOGGING_SIDE_METADATA_SPEC
+        .as_spec()
+        .extract_side_spec();
+    // The barriers test a single bit per object.
+    assert_eq!(log_bit.log_num_of_bits, 0);
+    let (slot_log_base, slot_log_byte_shift, slot_log_shift) =
+        crate::field_barrier::slot_log_geometry();
+    BarrierDescriptor {
+        kind: active(),
+        header_metadata: vm_metadata::header_metadata(),
+        log_bit_base: log_bit.get_absolute_offset(),
+        log_bit_byte_shift: log_bit.log_min_obj_size + LOG_BITS_IN_BYTE as usize,
+        log_bit_shift: log_bit.log_min_obj_size,
+        log_bit_mask: BITS_IN_BYTE - 1,
+        slot_log_base,
+        slot_log_byte_shift,
+        slot_log_shift,
+        forwarding_bits_shift: vm_metadata::FORWARDING_BITS_OFFSET as usize,
+        forwarding_bits_mask: vm_metadata::FORWARDING_BITS_MASK,
+        record_modified_nodes: crate::api::mmtk_record_modified_nodes,
+        record_modified_edges: crate::api::mmtk_record_modified_edges,
+        array_copy_pre: crate::api::array_copy_pre,
+        array_copy_post: crate::api::array_copy_post,
+        load_reference_slow: crate::api::mmtk_load_reference_slow,
+    }
+});
+
+/// The descriptor of the active barrier. Only valid after `openjdk_gc_init`.
+pub fn descriptor() -> &'static BarrierDescriptor {
+    &DESCRIPTOR
+}
@@ -1,31 +1,33 @@
+use libc::c_void;
 use mmtk::scheduler::{GCWorker, WorkBucketStage};
 use mmtk::scheduler::{ProcessEdgesWork, ScanStackRoot};
 use mmtk::util::opaque_pointer::*;
-use mmtk::vm::{Collection, Scanning, VMBinding};
+use mmtk::vm::{Collection, Scanning};
 use mmtk::{Mutator, MutatorContext};
 
+use crate::scanning::VMScanning;
 use crate::OpenJDK;
-use crate::{SINGLETON, UPCALLS};
+use crate::{singleton, UPCALLS};
 
 pub struct VMCollection {}
 
-extern "C" fn create_mutator_scan_work<E: ProcessEdgesWork<VM = OpenJDK>>(
-    mutator: &'static mut Mutator<OpenJDK>,
+extern "C" fn create_mutator_scan_work<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
+    mutator: &'static mut Mutator<OpenJDK<H>>,
 ) {
     mmtk::memory_manager::add_work_packet(
-        &SINGLETON,
+        singleton::<H>(),
         WorkBucketStage::Prepare,
         ScanStackRoot::<E>(mutator),
     );
 }
 
-impl Collection<OpenJDK> for VMCollection {
-    fn stop_all_mutators<E: ProcessEdgesWork<VM = OpenJDK>>(tls: VMWorkerThread) {
+impl<const H: bool> Collection<OpenJDK<H>> for VMCollection {
+    fn stop_all_mutators<E: ProcessEdgesWork<VM = OpenJDK<H>>>(tls: VMWorkerThread) {
         let f = {
-            if <OpenJDK as VMBinding>::VMScanning::SCAN_MUTATORS_IN_SAFEPOINT {
+            if <VMScanning as Scanning<OpenJDK<H>>>::SCAN_MUTATORS_IN_SAFEPOINT {
                 0usize as _
             } else {
-                create_mutator_scan_work::<E> as *const extern "C" fn(&'static mut Mutator<OpenJDK>)
+                create_mutator_scan_work::<E, H> as *const extern "C" fn(*mut c_void)
             }
         };
         unsafe {
@@ -34,9 +36,11 @@ impl Collection<OpenJDK> for VMCollection {
     }
 
     fn resume_mutators(tls: VMWorkerThread) {
+        crate::vm_metadata::restore_preserved_marks();
         unsafe {
             ((*UPCALLS).resume_mutators)(tls);
         }
+        crate::reference_processor::enqueue_pending_references();
     }
 
     fn block_for_gc(_tls: VMMutatorThread) {
@@ -45,9 +49,9 @@ impl Collection<OpenJDK> for VMCollection {
         }
     }
 
-    fn spawn_worker_thread(tls: VMThread, ctx: Option<&GCWorker<OpenJDK>>) {
+    fn spawn_worker_thread(tls: VMThread, ctx: Option<&GCWorker<OpenJDK<H>>>) {
         let ctx_ptr = if let Some(r) = ctx {
-            r as *const GCWorker<OpenJDK> as *mut GCWorker<OpenJDK>
+            r as *const GCWorker<OpenJDK<H>> as *mut GCWorker<OpenJDK<H>>
         } else {
             std::ptr::null_mut()
         };
@@ -56,7 +60,7 @@ impl Collection<OpenJDK> for VMCollection {
         }
     }
 
-    fn prepare_mutator<T: MutatorContext<OpenJDK>>(
+    fn prepare_mutator<T: MutatorContext<OpenJDK<H>>>(
         _tls_w: VMWorkerThread,
         _tls_m: VMMutatorThread,
         _m: &T,
@@ -0,0 +1,73 @@
+//! A pool of edge buffers.
+//!
+//! The VM fills edge buffers during root scanning (see `NewBuffer`), and object scanning fills
+//! them too. Each full buffer becomes an `OpenJDKProcessEdges` packet, which gives the buffer
+//! back once it is processed. The object barrier of the VM also logs modified objects in these
+//! buffers (see `mmtk_record_modified_nodes`). Buffers are pooled per thread, so GC workers
+//! never contend on the pool. A worker may get back more buffers than it takes, so each pool is
+//! bounded.
+
+use mmtk::util::Address;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The most buffers a thread keeps.
+const MAX_POOLED_BUFFERS: usize = 64;
+
+thread_local! {
+    static POOL: RefCell<Vec<Vec<Address>>> = RefCell::new(Vec::new());
+}
+
+static REUSED: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+/// An empty buffer with at least `capacity` slots.
+#[inline]
+pub fn take(capacity: usize) -> Vec<Address> {
+    let pooled = POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        match pool.last() {
+            Some(buffer) if buffer.capacity() >= capacity => pool.pop(),
+            _ => None,
+        }
+    });
+    match pooled {
+        Some(buffer) => {
+            REUSED.fetch_add(1, Ordering::Relaxed);
+            buffer
+        }
+        None => {
+            ALLOCATED.fetch_add(1, Ordering::Relaxed);
+            Vec::with_capacity(capacity)
+        }
+    }
+}
+
+/// Give a buffer back to the pool of the current thread.
+#[inline]
+pub fn recycle(mut buffer: Vec<Address>) {
+    if buffer.capacity() == 0 {
+        return;
+    }
+    buffer.clear();
+    POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        if pool.len() < MAX_POOLED_BUFFERS {
+            pool.push(buffer);
+        }
+    });
+}
+
+/// The number of buffers reused from the pools and newly allocated since the last call.
+#[repr(C)]
+pub struct EdgeBufferStats {
+    pub reused: usize,
+    pub allocated: usize,
+}
+
+pub fn take_stats() -> EdgeBufferStats {
+    EdgeBufferStats {
+        reused: REUSED.swap(0, Ordering::Relaxed),
+        allocated: ALLOCATED.swap(0, Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,177 @@
+//! The field-logging write barrier (`FieldBarrier`).
+//!
+//! The object barrier of generational plans remembers a whole mature object the first time
+//! one of its fields is written, and the next nursery GC scans all the fields of the object.
+//! For a large, mostly-old object array, that is a lot of slots to scan for a few writes.
+//! The field barrier remembers the written slots instead.
+//!
+//! It is selected with the `barrier` option, for plans that use the object barrier. It keeps
+//! the unlogged bit of the object barrier as its filter: the VM only takes the slow path for
+//! writes into objects whose bit is set, i.e. mature objects. The slow path then sets the log
+//! bit of the slot, in a bitmap of the binding with one bit per slot, and only logs the slot if
+//! the bit was clear. So a slot is logged at most once between two GCs. Writes that the VM does
+//! not log slot by slot (clones) still go through the object barrier.
+//!
+//! Each mutator logs slots in a buffer of the VM, and hands it over when it is full, or when the
+//! mutators are stopped for a GC (see `mmtk_record_modified_edges` in `api`). Every GC clears
+//! the log bits of the slots, and a nursery GC processes them as roots. The slots of array copies
+//! are logged by the binding instead (see `array_copy_post` in `api`). The object barrier logs
+//! them too, for the array copies of the VM that do not know the destination array.
+
+use crate::barriers::Barrier;
+use crate::gc_work::OpenJDKProcessEdges;
+use crate::{singleton, OpenJDK};
+use mmtk::memory_manager;
+use mmtk::scheduler::{GCWork, GCWorker, ProcessEdgesWork, WorkBucketStage};
+use mmtk::util::constants::{BITS_IN_BYTE, LOG_BITS_IN_BYTE, LOG_BYTES_IN_ADDRESS};
+use mmtk::util::Address;
+use mmtk::MMTK;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+
+lazy_static! {
+    /// The buffers handed over since the last GC.
+    static ref LOGGED_SLOTS: Mutex<Vec<Vec<Address>>> = Mutex::new(vec![]);
+}
+
+/// The log bit of `slot` is bit `(slot >> LOG_SLOT_SIZE) % 8` of the byte at
+/// `SLOT_LOG_BASE + (slot >> (LOG_SLOT_SIZE + 3))`.
+static mut SLOT_LOG_BASE: Address = Address::ZERO;
+static mut LOG_SLOT_SIZE: usize = 0;
+
+/// Whether the VM may log slots. Only then do GCs process them.
+#[inline(always)]
+pub fn logs_slots() -> bool {
+    matches!(
+        crate::barriers::active(),
+        Barrier::ObjectBarrier | Barrier::FieldBarrier
+    )
+}
+
+/// Map the log bits of the slots of the heap, if the active barrier logs slots. This is called
+/// from `openjdk_gc_init`, after the barrier is selected. Like side metadata, the bitmap is
+/// reserved for the whole heap range, and only the pages of the logged slots are touched.
+pub fn initialize() {
+    if !logs_slots() {
+        return;
+    }
+    let log_slot_size = LOG_BYTES_IN_ADDRESS as usize;
+    let byte_shift = log_slot_size + LOG_BITS_IN_BYTE as usize;
+    let start = memory_manager::starting_heap_address();
+    let end = memory_manager::last_heap_address();
+    let size = (end - start) >> byte_shift;
+    let bitmap = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+            -1,
+            0,
+        )
+    };
+    assert!(
+        bitmap != libc::MAP_FAILED,
+        "Failed to map the log bits of the slots"
+    );
+    unsafe {
+        LOG_SLOT_SIZE = log_slot_size;
+        SLOT_LOG_BASE = Address::from_mut_ptr(bitmap) - (start.as_usize() >> byte_shift);
+    }
+}
+
+/// The geometry of the log bits of the slots, for the barrier descriptor: the base, the byte
+/// shift and the bit shift.
+pub fn slot_log_geometry() -> (Address, usize, usize) {
+    unsafe {
+        (
+            SLOT_LOG_BASE,
+            LOG_SLOT_SIZE + LOG_BITS_IN_BYTE as usize,
+            LOG_SLOT_SIZE,
+        )
+    }
+}
+
+/// The byte and the bit of the log bit of `slot`.
+#[inline(always)]
+fn log_bit(slot: Address) -> (&'static AtomicU8, u8) {
+    let (base, byte_shift, shift) = slot_log_geometry();
+    let byte = unsafe { &*(base + (slot.as_usize() >> byte_shift)).to_ptr::<AtomicU8>() };
+    (
+        byte,
+        1u8 << ((slot.as_usize() >> shift) & (BITS_IN_BYTE - 1)),
+    )
+}
+
+/// Clear the log bit of `slot`, so that the next write into it is logged again.
+#[inline(always)]
+fn clear_log_bit(slot: Address) {
+    let (byte, bit) = log_bit(slot);
+    byte.fetch_and(!bit, Ordering::Relaxed);
+}
+
+/// Set the log bit of `slot`, like the slow path of the barrier in the VM. Returns whether it
+/// was clear, i.e. whether the caller has to log the slot.
+#[inline(always)]
+fn log_slot(slot: Address) -> bool {
+    let (byte, bit) = log_bit(slot);
+    byte.load(Ordering::Relaxed) & bit == 0 && byte.fetch_or(bit, Ordering::SeqCst) & bit == 0
+}
+
+/// Log the `count` slots from `slots` on, after an array copy. Null slots are left out: a later
+/// write into one of them goes through the barrier.
+pub fn log_copied_slots(slots: Address, count: usize) {
+    let mut logged = vec![];
+    for i in 0..count {
+        let slot = slots + (i << LOG_BYTES_IN_ADDRESS);
+        if !unsafe { slot.load::<Address>() }.is_zero() && log_slot(slot) {
+            logged.push(slot);
+        }
+    }
+    if !logged.is_empty() {
+        add_logged_slots(logged);
+    }
+}
+
+/// A buffer of slots handed over by the VM.
+pub fn add_logged_slots(slots: Vec<Address>) {
+    LOGGED_SLOTS.lock().unwrap().push(slots);
+}
+
+/// Clears the log bits of the slots logged since the last GC. Then it turns the slots into edge
+/// packets in a nursery GC, and drops them in a full-heap GC, which finds all the live objects
+/// anyway.
+pub struct ProcessLoggedSlots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ProcessLoggedSlots<E, H> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ProcessLoggedSlots<E, H>
+{
+    fn do_work(&mut self, worker: &mut GCWorker<OpenJDK<H>>, mmtk: &'static MMTK<OpenJDK<H>>) {
+        let buffers = mem::take(&mut *LOGGED_SLOTS.lock().unwrap());
+        let nursery = mmtk.get_plan().is_current_gc_nursery();
+        for slots in buffers {
+            for slot in &slots {
+                clear_log_bit(*slot);
+            }
+            if nursery {
+                for chunk in slots.chunks(E::CAPACITY) {
+                    let mut edges = crate::edge_buffer::take(E::CAPACITY);
+                    edges.extend_from_slice(chunk);
+                    worker.add_work(
+                        WorkBucketStage::Closure,
+                        OpenJDKProcessEdges::<E>::new(edges, false, singleton::<H>()),
+                    );
+                }
+            }
+            crate::edge_buffer::recycle(slots);
+        }
+    }
+}
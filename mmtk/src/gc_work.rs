@@ -1,197 +1,364 @@
 use super::{OpenJDK, UPCALLS};
+use crate::prefetch;
 use crate::scanning::create_process_edges_work;
+use libc::c_void;
 use mmtk::scheduler::*;
+use mmtk::util::{Address, ObjectReference};
+use mmtk::vm::VMBinding;
 use mmtk::MMTK;
 use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::time::Instant;
+
+/// Processes the edge buffers created by the binding.
+///
+/// This packet prefetches the slots ahead of tracing them, and recycles the buffer afterwards. It
+/// delegates the tracing to the plan's own `ProcessEdgesWork`, so newly reached objects are
+/// still scanned by the plan.
+pub struct OpenJDKProcessEdges<E: ProcessEdgesWork> {
+    base: ProcessEdgesBase<Self>,
+    inner: E,
+}
+
+impl<E: ProcessEdgesWork> ProcessEdgesWork for OpenJDKProcessEdges<E> {
+    type VM = E::VM;
+    const CAPACITY: usize = E::CAPACITY;
+    const OVERWRITE_REFERENCE: bool = E::OVERWRITE_REFERENCE;
+    const SCAN_OBJECTS_IMMEDIATELY: bool = E::SCAN_OBJECTS_IMMEDIATELY;
+
+    fn new(edges: Vec<Address>, roots: bool, mmtk: &'static MMTK<E::VM>) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges, mmtk),
+            inner: E::new(vec![], roots, mmtk),
+        }
+    }
+
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        self.inner.trace_object(object)
+    }
+
+    #[inline]
+    fn process_edge(&mut self, slot: Address) {
+        let object = unsafe { slot.load::<ObjectReference>() };
+        let new_object = self.trace_object(object);
+        if Self::OVERWRITE_REFERENCE {
+            unsafe { slot.store(new_object) };
+        }
+    }
 
-pub struct ScanUniverseRoots<E: ProcessEdgesWork<VM = OpenJDK>>(PhantomData<E>);
+    #[inline]
+    fn process_edges(&mut self) {
+        let start = Instant::now();
+        self.inner.set_worker(self.base.worker());
+        let edges = mem::take(&mut self.edges);
+        prefetch::for_each(&edges, prefetch::prefetch_edge_target, |edge| {
+            self.process_edge(edge)
+        });
+        self.flush();
+        crate::edge_buffer::recycle(edges);
+        crate::object_scanning::record_trace_time(start);
+    }
+
+    #[inline]
+    fn flush(&mut self) {
+        // Objects reached by this packet were enqueued by the inner packet.
+        if !self.inner.nodes.is_empty() {
+            self.inner.flush();
+        }
+    }
+}
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> ScanUniverseRoots<E> {
+impl<E: ProcessEdgesWork> Deref for OpenJDKProcessEdges<E> {
+    type Target = ProcessEdgesBase<Self>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<E: ProcessEdgesWork> DerefMut for OpenJDKProcessEdges<E> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+pub struct ScanUniverseRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ScanUniverseRoots<E, H> {
     pub fn new() -> Self {
         Self(PhantomData)
     }
 }
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> GCWork<OpenJDK> for ScanUniverseRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK>, _mmtk: &'static MMTK<OpenJDK>) {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanUniverseRoots<E, H>
+{
+    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK<H>>, _mmtk: &'static MMTK<OpenJDK<H>>) {
         unsafe {
-            ((*UPCALLS).scan_universe_roots)(create_process_edges_work::<E> as _);
+            ((*UPCALLS).scan_universe_roots)(create_process_edges_work::<E, H> as _);
         }
     }
 }
 
-pub struct ScanJNIHandlesRoots<E: ProcessEdgesWork<VM = OpenJDK>>(PhantomData<E>);
+pub struct ScanJNIHandlesRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(PhantomData<E>);
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> ScanJNIHandlesRoots<E> {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ScanJNIHandlesRoots<E, H> {
     pub fn new() -> Self {
         Self(PhantomData)
     }
 }
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> GCWork<OpenJDK> for ScanJNIHandlesRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK>, _mmtk: &'static MMTK<OpenJDK>) {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanJNIHandlesRoots<E, H>
+{
+    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK<H>>, _mmtk: &'static MMTK<OpenJDK<H>>) {
         unsafe {
-            ((*UPCALLS).scan_jni_handle_roots)(create_process_edges_work::<E> as _);
+            ((*UPCALLS).scan_jni_handle_roots)(create_process_edges_work::<E, H> as _);
         }
     }
 }
 
-pub struct ScanObjectSynchronizerRoots<E: ProcessEdgesWork<VM = OpenJDK>>(PhantomData<E>);
+pub struct ScanObjectSynchronizerRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
+    PhantomData<E>,
+);
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> ScanObjectSynchronizerRoots<E> {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ScanObjectSynchronizerRoots<E, H> {
     pub fn new() -> Self {
         Self(PhantomData)
     }
 }
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> GCWork<OpenJDK> for ScanObjectSynchronizerRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK>, _mmtk: &'static MMTK<OpenJDK>) {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanObjectSynchronizerRoots<E, H>
+{
+    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK<H>>, _mmtk: &'static MMTK<OpenJDK<H>>) {
         unsafe {
-            ((*UPCALLS).scan_object_synchronizer_roots)(create_process_edges_work::<E> as _);
+            ((*UPCALLS).scan_object_synchronizer_roots)(create_process_edges_work::<E, H> as _);
         }
     }
 }
 
-pub struct ScanManagementRoots<E: ProcessEdgesWork<VM = OpenJDK>>(PhantomData<E>);
+pub struct ScanManagementRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(PhantomData<E>);
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> ScanManagementRoots<E> {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ScanManagementRoots<E, H> {
     pub fn new() -> Self {
         Self(PhantomData)
     }
 }
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> GCWork<OpenJDK> for ScanManagementRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK>, _mmtk: &'static MMTK<OpenJDK>) {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanManagementRoots<E, H>
+{
+    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK<H>>, _mmtk: &'static MMTK<OpenJDK<H>>) {
         unsafe {
-            ((*UPCALLS).scan_management_roots)(create_process_edges_work::<E> as _);
+            ((*UPCALLS).scan_management_roots)(create_process_edges_work::<E, H> as _);
         }
     }
 }
 
-pub struct ScanJvmtiExportRoots<E: ProcessEdgesWork<VM = OpenJDK>>(PhantomData<E>);
+pub struct ScanJvmtiExportRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
+    PhantomData<E>,
+);
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> ScanJvmtiExportRoots<E> {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ScanJvmtiExportRoots<E, H> {
     pub fn new() -> Self {
         Self(PhantomData)
     }
 }
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> GCWork<OpenJDK> for ScanJvmtiExportRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK>, _mmtk: &'static MMTK<OpenJDK>) {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanJvmtiExportRoots<E, H>
+{
+    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK<H>>, _mmtk: &'static MMTK<OpenJDK<H>>) {
         unsafe {
-            ((*UPCALLS).scan_jvmti_export_roots)(create_process_edges_work::<E> as _);
+            ((*UPCALLS).scan_jvmti_export_roots)(create_process_edges_work::<E, H> as _);
         }
     }
 }
 
-pub struct ScanAOTLoaderRoots<E: ProcessEdgesWork<VM = OpenJDK>>(PhantomData<E>);
+pub struct ScanAOTLoaderRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(PhantomData<E>);
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> ScanAOTLoaderRoots<E> {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ScanAOTLoaderRoots<E, H> {
     pub fn new() -> Self {
         Self(PhantomData)
     }
 }
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> GCWork<OpenJDK> for ScanAOTLoaderRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK>, _mmtk: &'static MMTK<OpenJDK>) {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanAOTLoaderRoots<E, H>
+{
+    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK<H>>, _mmtk: &'static MMTK<OpenJDK<H>>) {
         unsafe {
-            ((*UPCALLS).scan_aot_loader_roots)(create_process_edges_work::<E> as _);
+            ((*UPCALLS).scan_aot_loader_roots)(create_process_edges_work::<E, H> as _);
         }
     }
 }
 
-pub struct ScanSystemDictionaryRoots<E: ProcessEdgesWork<VM = OpenJDK>>(PhantomData<E>);
+pub struct ScanSystemDictionaryRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
+    PhantomData<E>,
+);
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> ScanSystemDictionaryRoots<E> {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ScanSystemDictionaryRoots<E, H> {
     pub fn new() -> Self {
         Self(PhantomData)
     }
 }
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> GCWork<OpenJDK> for ScanSystemDictionaryRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK>, _mmtk: &'static MMTK<OpenJDK>) {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanSystemDictionaryRoots<E, H>
+{
+    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK<H>>, _mmtk: &'static MMTK<OpenJDK<H>>) {
         unsafe {
-            ((*UPCALLS).scan_system_dictionary_roots)(create_process_edges_work::<E> as _);
+            ((*UPCALLS).scan_system_dictionary_roots)(create_process_edges_work::<E, H> as _);
         }
     }
 }
 
-pub struct ScanCodeCacheRoots<E: ProcessEdgesWork<VM = OpenJDK>>(PhantomData<E>);
+pub struct ScanCodeCacheRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(PhantomData<E>);
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> ScanCodeCacheRoots<E> {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ScanCodeCacheRoots<E, H> {
     pub fn new() -> Self {
         Self(PhantomData)
     }
 }
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> GCWork<OpenJDK> for ScanCodeCacheRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK>, _mmtk: &'static MMTK<OpenJDK>) {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanCodeCacheRoots<E, H>
+{
+    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK<H>>, _mmtk: &'static MMTK<OpenJDK<H>>) {
         unsafe {
-            ((*UPCALLS).scan_code_cache_roots)(create_process_edges_work::<E> as _);
+            ((*UPCALLS).scan_code_cache_roots)(create_process_edges_work::<E, H> as _);
         }
     }
 }
 
-pub struct ScanStringTableRoots<E: ProcessEdgesWork<VM = OpenJDK>>(PhantomData<E>);
+pub struct ScanStringTableRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
+    PhantomData<E>,
+);
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> ScanStringTableRoots<E> {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ScanStringTableRoots<E, H> {
     pub fn new() -> Self {
         Self(PhantomData)
     }
 }
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> GCWork<OpenJDK> for ScanStringTableRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK>, _mmtk: &'static MMTK<OpenJDK>) {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanStringTableRoots<E, H>
+{
+    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK<H>>, _mmtk: &'static MMTK<OpenJDK<H>>) {
         unsafe {
-            ((*UPCALLS).scan_string_table_roots)(create_process_edges_work::<E> as _);
+            ((*UPCALLS).scan_string_table_roots)(create_process_edges_work::<E, H> as _);
         }
     }
 }
 
-pub struct ScanClassLoaderDataGraphRoots<E: ProcessEdgesWork<VM = OpenJDK>>(PhantomData<E>);
+pub struct ScanClassLoaderDataGraphRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
+    PhantomData<E>,
+);
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> ScanClassLoaderDataGraphRoots<E> {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ScanClassLoaderDataGraphRoots<E, H> {
     pub fn new() -> Self {
         Self(PhantomData)
     }
 }
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> GCWork<OpenJDK> for ScanClassLoaderDataGraphRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK>, _mmtk: &'static MMTK<OpenJDK>) {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanClassLoaderDataGraphRoots<E, H>
+{
+    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK<H>>, mmtk: &'static MMTK<OpenJDK<H>>) {
         unsafe {
-            ((*UPCALLS).scan_class_loader_data_graph_roots)(create_process_edges_work::<E> as _);
+            ((*UPCALLS).scan_class_loader_data_graph_roots)(
+                create_process_edges_work::<E, H> as _,
+                is_full_heap_gc(mmtk),
+            );
         }
     }
 }
 
-pub struct ScanWeakProcessorRoots<E: ProcessEdgesWork<VM = OpenJDK>>(PhantomData<E>);
+pub struct ScanWeakProcessorRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
+    PhantomData<E>,
+);
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> ScanWeakProcessorRoots<E> {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ScanWeakProcessorRoots<E, H> {
     pub fn new() -> Self {
         Self(PhantomData)
     }
 }
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> GCWork<OpenJDK> for ScanWeakProcessorRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK>, _mmtk: &'static MMTK<OpenJDK>) {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanWeakProcessorRoots<E, H>
+{
+    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK<H>>, _mmtk: &'static MMTK<OpenJDK<H>>) {
         unsafe {
-            ((*UPCALLS).scan_weak_processor_roots)(create_process_edges_work::<E> as _);
+            ((*UPCALLS).scan_weak_processor_roots)(create_process_edges_work::<E, H> as _);
         }
     }
 }
 
-pub struct ScanVMThreadRoots<E: ProcessEdgesWork<VM = OpenJDK>>(PhantomData<E>);
+pub struct ScanVMThreadRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ScanVMThreadRoots<E, H> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanVMThreadRoots<E, H>
+{
+    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK<H>>, _mmtk: &'static MMTK<OpenJDK<H>>) {
+        unsafe {
+            ((*UPCALLS).scan_vm_thread_roots)(create_process_edges_work::<E, H> as _);
+        }
+    }
+}
+
+/// Only full-heap GCs trace all the live objects, so only they can tell whether an object
+/// reached from a weak root, or a class, is dead. Nursery GCs keep the weak roots strong and
+/// never unload classes. Unloading further requires `-XX:+ClassUnloading`.
+pub fn is_full_heap_gc<VM: VMBinding>(mmtk: &'static MMTK<VM>) -> bool {
+    !mmtk.get_plan().is_current_gc_nursery()
+}
+
+extern "C" fn is_alive(object: ObjectReference) -> bool {
+    object.is_live()
+}
+
+extern "C" fn forward_object<E: ProcessEdgesWork>(
+    trace: *mut c_void,
+    object: ObjectReference,
+) -> ObjectReference {
+    // Tracing a live object only returns its new location.
+    unsafe { &mut *(trace as *mut E) }.trace_object(object)
+}
+
+/// Processes the string table and the weak processor roots after the closure of a full-heap
+/// GC: clears the dead entries and updates the others. Then unloads the classes whose
+/// `ClassLoaderData` was not reached.
+pub struct ProcessWeakRoots<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(PhantomData<E>);
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> ScanVMThreadRoots<E> {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ProcessWeakRoots<E, H> {
     pub fn new() -> Self {
         Self(PhantomData)
     }
 }
 
-impl<E: ProcessEdgesWork<VM = OpenJDK>> GCWork<OpenJDK> for ScanVMThreadRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<OpenJDK>, _mmtk: &'static MMTK<OpenJDK>) {
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ProcessWeakRoots<E, H>
+{
+    fn do_work(&mut self, worker: &mut GCWorker<OpenJDK<H>>, mmtk: &'static MMTK<OpenJDK<H>>) {
+        let mut trace = E::new(vec![], false, mmtk);
+        trace.set_worker(worker);
         unsafe {
-            ((*UPCALLS).scan_vm_thread_roots)(create_process_edges_work::<E> as _);
+            ((*UPCALLS).process_weak_roots)(
+                &mut trace as *mut E as *mut c_void,
+                is_alive,
+                forward_object::<E>,
+            );
         }
+        debug_assert!(trace.nodes.is_empty());
     }
 }
@@ -4,6 +4,7 @@
 #![feature(box_syntax)]
 #![feature(vec_into_raw_parts)]
 #![feature(once_cell)]
+#![feature(core_intrinsics)]
 
 extern crate libc;
 extern crate mmtk;
@@ -12,21 +13,50 @@ extern crate lazy_static;
 
 use std::ptr::null_mut;
 
-use libc::{c_char, c_void, uintptr_t};
-use mmtk::scheduler::GCWorker;
+use libc::{c_char, c_void};
 use mmtk::util::opaque_pointer::*;
 use mmtk::util::{Address, ObjectReference};
 use mmtk::vm::VMBinding;
-use mmtk::Mutator;
 use mmtk::MMTK;
+
+/// Evaluate `$body` with `$vm` bound to the binding instance for the metadata layout chosen at
+/// `openjdk_gc_init`, and `$singleton` bound to its MMTk instance.
+macro_rules! with_singleton {
+    (|$vm:ident, $singleton:ident| $body:expr) => {
+        if crate::vm_metadata::header_metadata() {
+            #[allow(dead_code)]
+            type $vm = crate::OpenJDK<true>;
+            #[allow(unused_variables)]
+            let $singleton = crate::singleton::<true>();
+            $body
+        } else {
+            #[allow(dead_code)]
+            type $vm = crate::OpenJDK<false>;
+            #[allow(unused_variables)]
+            let $singleton = crate::singleton::<false>();
+            $body
+        }
+    };
+}
+
 mod abi;
 pub mod active_plan;
 pub mod api;
+mod barriers;
 pub mod collection;
+mod edge_buffer;
+mod field_barrier;
 mod gc_work;
+mod load_reference_barrier;
+mod object_barrier;
+mod object_copy;
 pub mod object_model;
 mod object_scanning;
+mod options;
+mod prefetch;
 pub mod reference_glue;
+mod reference_processor;
+mod scan_descriptor;
 pub mod scanning;
 pub(crate) mod vm_metadata;
 
@@ -42,12 +72,12 @@ type ProcessEdgesFn = *const extern "C" fn(buf: *mut Address, size: usize, cap:
 pub struct OpenJDK_Upcalls {
     pub stop_all_mutators: extern "C" fn(
         tls: VMWorkerThread,
-        create_stack_scan_work: *const extern "C" fn(&'static mut Mutator<OpenJDK>),
+        create_stack_scan_work: *const extern "C" fn(*mut c_void),
     ),
     pub resume_mutators: extern "C" fn(tls: VMWorkerThread),
-    pub spawn_worker_thread: extern "C" fn(tls: VMThread, ctx: *mut GCWorker<OpenJDK>),
+    pub spawn_worker_thread: extern "C" fn(tls: VMThread, ctx: *mut c_void),
     pub block_for_gc: extern "C" fn(),
-    pub get_next_mutator: extern "C" fn() -> *mut Mutator<OpenJDK>,
+    pub get_next_mutator: extern "C" fn() -> *mut c_void,
     pub reset_mutator_iterator: extern "C" fn(),
     pub compute_static_roots: extern "C" fn(trace: *mut c_void, tls: OpaquePointer),
     pub compute_global_roots: extern "C" fn(trace: *mut c_void, tls: OpaquePointer),
@@ -55,7 +85,7 @@ pub struct OpenJDK_Upcalls {
     pub scan_object: extern "C" fn(trace: *mut c_void, object: ObjectReference, tls: OpaquePointer),
     pub dump_object: extern "C" fn(object: ObjectReference),
     pub get_object_size: extern "C" fn(object: ObjectReference) -> usize,
-    pub get_mmtk_mutator: extern "C" fn(tls: VMMutatorThread) -> *mut Mutator<OpenJDK>,
+    pub get_mmtk_mutator: extern "C" fn(tls: VMMutatorThread) -> *mut c_void,
     pub is_mutator: extern "C" fn(tls: VMThread) -> bool,
     pub enter_vm: extern "C" fn() -> i32,
     pub leave_vm: extern "C" fn(st: i32),
@@ -76,42 +106,72 @@ pub struct OpenJDK_Upcalls {
     pub scan_system_dictionary_roots: extern "C" fn(process_edges: ProcessEdgesFn),
     pub scan_code_cache_roots: extern "C" fn(process_edges: ProcessEdgesFn),
     pub scan_string_table_roots: extern "C" fn(process_edges: ProcessEdgesFn),
-    pub scan_class_loader_data_graph_roots: extern "C" fn(process_edges: ProcessEdgesFn),
+    pub scan_class_loader_data_graph_roots:
+        extern "C" fn(process_edges: ProcessEdgesFn, unload_classes: bool),
     pub scan_weak_processor_roots: extern "C" fn(process_edges: ProcessEdgesFn),
     pub scan_vm_thread_roots: extern "C" fn(process_edges: ProcessEdgesFn),
     pub number_of_mutators: extern "C" fn() -> usize,
     pub schedule_finalizer: extern "C" fn(),
+    pub klass_offset_in_mirror: extern "C" fn() -> i32,
+    pub enqueue_references: extern "C" fn(head: ObjectReference, tail: ObjectReference),
+    pub scan_cld: extern "C" fn(process_edges: ProcessEdgesFn, cld: Address),
+    pub loader_data: extern "C" fn(loader: ObjectReference) -> Address,
+    pub process_weak_roots: extern "C" fn(
+        trace: *mut c_void,
+        is_alive: extern "C" fn(ObjectReference) -> bool,
+        forward: extern "C" fn(*mut c_void, ObjectReference) -> ObjectReference,
+    ),
 }
 
 pub static mut UPCALLS: *const OpenJDK_Upcalls = null_mut();
 
 #[no_mangle]
-pub static GLOBAL_SIDE_METADATA_VM_BASE_ADDRESS: uintptr_t =
-    crate::mmtk::util::metadata::side_metadata::GLOBAL_SIDE_METADATA_VM_BASE_ADDRESS.as_usize();
+pub static MMTK_MARK_COMPACT_HEADER_RESERVED_IN_BYTES: usize =
+    vm_metadata::MARK_COMPACT_HEADER_RESERVED_IN_BYTES;
 
+/// The OpenJDK binding. `HEADER_METADATA` selects the metadata layout, see `vm_metadata::layout`.
 #[derive(Default)]
-pub struct OpenJDK;
+pub struct OpenJDK<const HEADER_METADATA: bool>;
 
-impl VMBinding for OpenJDK {
-    type VMObjectModel = object_model::VMObjectModel;
+impl<const H: bool> VMBinding for OpenJDK<H> {
+    type VMObjectModel = object_model::VMObjectModel<H>;
     type VMScanning = scanning::VMScanning;
     type VMCollection = collection::VMCollection;
     type VMActivePlan = active_plan::VMActivePlan;
     type VMReferenceGlue = reference_glue::VMReferenceGlue;
 }
 
+fn create_mmtk<VM: VMBinding>() -> MMTK<VM> {
+    #[cfg(feature = "nogc")]
+    std::env::set_var("MMTK_PLAN", "NoGC");
+    #[cfg(feature = "semispace")]
+    std::env::set_var("MMTK_PLAN", "SemiSpace");
+    #[cfg(feature = "gencopy")]
+    std::env::set_var("MMTK_PLAN", "GenCopy");
+    #[cfg(feature = "marksweep")]
+    std::env::set_var("MMTK_PLAN", "MarkSweep");
+    #[cfg(feature = "pageprotect")]
+    std::env::set_var("MMTK_PLAN", "PageProtect");
+    #[cfg(feature = "markcompact")]
+    std::env::set_var("MMTK_PLAN", "MarkCompact");
+    MMTK::new()
+}
+
 lazy_static! {
-    pub static ref SINGLETON: MMTK<OpenJDK> = {
-        #[cfg(feature = "nogc")]
-        std::env::set_var("MMTK_PLAN", "NoGC");
-        #[cfg(feature = "semispace")]
-        std::env::set_var("MMTK_PLAN", "SemiSpace");
-        #[cfg(feature = "gencopy")]
-        std::env::set_var("MMTK_PLAN", "GenCopy");
-        #[cfg(feature = "marksweep")]
-        std::env::set_var("MMTK_PLAN", "MarkSweep");
-        #[cfg(feature = "pageprotect")]
-        std::env::set_var("MMTK_PLAN", "PageProtect");
-        MMTK::new()
-    };
+    // Only the instance for the selected layout is ever created.
+    pub static ref SINGLETON_HEADER: MMTK<OpenJDK<true>> = create_mmtk();
+    pub static ref SINGLETON_SIDE: MMTK<OpenJDK<false>> = create_mmtk();
+}
+
+/// The MMTk instance for a metadata layout.
+#[inline(always)]
+pub fn singleton<const H: bool>() -> &'static MMTK<OpenJDK<H>> {
+    // The casts do not change the type: `H` is the constant we branch on.
+    unsafe {
+        if H {
+            &*(&*SINGLETON_HEADER as *const MMTK<OpenJDK<true>> as *const MMTK<OpenJDK<H>>)
+        } else {
+            &*(&*SINGLETON_SIDE as *const MMTK<OpenJDK<false>> as *const MMTK<OpenJDK<H>>)
+        }
+    }
 }
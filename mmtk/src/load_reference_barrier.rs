@@ -0,0 +1,33 @@
+//! The load reference barrier (`LoadReferenceBarrier`).
+//!
+//! A plan that copies objects while the mutators run has to keep them from using a stale copy.
+//! With this barrier, the VM resolves every reference it loads from the heap to the current copy
+//! of the object: if the forwarding bits of the object (see `vm_metadata`) say it has been
+//! forwarded, the reference is replaced by the forwarding pointer.
+//!
+//! It is selected with `MMTK_BARRIER=LoadReferenceBarrier`. None of the plans of the mmtk-core
+//! version we build against copies concurrently, so the mutators never see a forwarded object and
+//! the barrier always returns the reference it is given. It can be run with SemiSpace to test the
+//! VM side, which emits it in the runtime, the interpreter, C1 and C2.
+
+use mmtk::util::object_forwarding;
+use mmtk::util::ObjectReference;
+use mmtk::vm::VMBinding;
+use std::sync::atomic;
+
+/// The slow path of the barrier: the current copy of `object`, which may be null. If a GC
+/// thread is copying the object, wait for the copy to be done.
+pub fn resolve<VM: VMBinding>(object: ObjectReference) -> ObjectReference {
+    if object.is_null() {
+        return object;
+    }
+    loop {
+        if object_forwarding::is_forwarded::<VM>(object) {
+            return object_forwarding::read_forwarding_pointer::<VM>(object);
+        }
+        if !object_forwarding::is_forwarded_or_being_forwarded::<VM>(object) {
+            return object;
+        }
+        atomic::spin_loop_hint();
+    }
+}
@@ -0,0 +1,105 @@
+//! The objects logged by the object barrier (`ObjectBarrier`).
+//!
+//! The VM takes the slow path of the barrier for writes into objects whose unlogged bit is set,
+//! i.e. mature objects. The slow path clears the bit, and logs the object only if it was set, so
+//! an object is logged at most once between two GCs. Each mutator logs objects in a buffer of the
+//! VM, and hands it over when it is full, or when the mutators are stopped for a GC (see
+//! `mmtk_record_modified_nodes` in `api`). Every GC sets the bits of the objects again, and a
+//! nursery GC scans them as roots. The field barrier logs objects here too, for clones.
+
+use crate::barriers::{self, Barrier};
+use crate::OpenJDK;
+use mmtk::scheduler::{GCWork, GCWorker, ProcessEdgesWork};
+use mmtk::util::{Address, ObjectReference};
+use mmtk::MMTK;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+
+lazy_static! {
+    /// The buffers handed over since the last GC.
+    static ref LOGGED_NODES: Mutex<Vec<Vec<Address>>> = Mutex::new(vec![]);
+}
+
+/// Whether the VM may log objects. Only then do GCs process them.
+#[inline(always)]
+pub fn logs_nodes() -> bool {
+    matches!(
+        barriers::active(),
+        Barrier::ObjectBarrier | Barrier::FieldBarrier
+    )
+}
+
+/// The byte and the bit of the unlogged bit of `object`.
+#[inline(always)]
+fn unlogged_bit(object: Address) -> (&'static AtomicU8, u8) {
+    let barrier = barriers::descriptor();
+    let addr = object.as_usize();
+    let byte = unsafe {
+        &*(barrier.log_bit_base + (addr >> barrier.log_bit_byte_shift)).to_ptr::<AtomicU8>()
+    };
+    (
+        byte,
+        1 << ((addr >> barrier.log_bit_shift) & barrier.log_bit_mask),
+    )
+}
+
+/// Set the unlogged bit of `object`, so that the next write into it is logged again.
+#[inline(always)]
+fn set_unlogged(object: Address) {
+    let (byte, bit) = unlogged_bit(object);
+    byte.fetch_or(bit, Ordering::SeqCst);
+}
+
+/// Whether the unlogged bit of `object` is set, i.e. it is mature and not logged yet.
+#[inline(always)]
+pub fn is_unlogged(object: Address) -> bool {
+    let (byte, bit) = unlogged_bit(object);
+    byte.load(Ordering::Relaxed) & bit != 0
+}
+
+/// Clear the unlogged bit of `object`, like the slow path of the barrier in the VM. Returns
+/// whether it was set, i.e. whether the caller has to log the object.
+#[inline(always)]
+pub fn log_object(object: Address) -> bool {
+    let (byte, bit) = unlogged_bit(object);
+    byte.load(Ordering::Relaxed) & bit != 0 && byte.fetch_and(!bit, Ordering::SeqCst) & bit != 0
+}
+
+/// A buffer of objects handed over by the VM.
+pub fn add_logged_nodes(objects: Vec<Address>) {
+    LOGGED_NODES.lock().unwrap().push(objects);
+}
+
+/// Sets the unlogged bits of the objects logged since the last GC. Then it scans the objects in
+/// a nursery GC, and drops them in a full-heap GC, which finds all the live objects anyway.
+pub struct ProcessLoggedNodes<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ProcessLoggedNodes<E, H> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ProcessLoggedNodes<E, H>
+{
+    fn do_work(&mut self, worker: &mut GCWorker<OpenJDK<H>>, mmtk: &'static MMTK<OpenJDK<H>>) {
+        let buffers = mem::take(&mut *LOGGED_NODES.lock().unwrap());
+        let nursery = mmtk.get_plan().is_current_gc_nursery();
+        for objects in buffers {
+            for object in &objects {
+                set_unlogged(*object);
+            }
+            if nursery {
+                let nodes: Vec<ObjectReference> = objects
+                    .iter()
+                    .map(|object| unsafe { object.to_object_reference() })
+                    .collect();
+                crate::object_scanning::scan_objects_and_create_edges_work::<E, H>(&nodes, worker);
+            }
+            crate::edge_buffer::recycle(objects);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Raw object copying used by the moving plans.
+//!
+//! HotSpot objects are always `HeapWord` aligned and their sizes are a whole number of
+//! words, so we never need to copy at byte granularity. Objects are split into three
+//! classes:
+//!
+//! * tiny objects (the vast majority of Java objects) are copied with an inlined word
+//!   loop, which avoids the call and dispatch overhead of `memcpy` for a handful of words;
+//! * medium objects are handed to `memcpy`, which uses the widest vector moves available;
+//! * large objects are copied with non-temporal stores (on x86_64) so that copying a big
+//!   array does not flush the rest of the GC working set out of the cache.
+
+use mmtk::util::constants::{BYTES_IN_WORD, LOG_BYTES_IN_WORD};
+use mmtk::util::Address;
+use std::ptr;
+
+/// Objects up to this size (in bytes) are copied with an inlined word loop.
+pub const TINY_OBJECT_BYTES: usize = 8 << LOG_BYTES_IN_WORD;
+
+/// Objects of at least this size (in bytes) are copied with non-temporal stores.
+pub const LARGE_OBJECT_BYTES: usize = 256 << 10;
+
+/// Copy `bytes` bytes from `src` to `dst`.
+///
+/// # Safety
+/// `src` and `dst` must be word aligned, `bytes` must be a multiple of the word size, and the
+/// two ranges must not overlap.
+#[inline(always)]
+pub unsafe fn copy_object(src: Address, dst: Address, bytes: usize) {
+    debug_assert!(src.is_aligned_to(BYTES_IN_WORD) && dst.is_aligned_to(BYTES_IN_WORD));
+    debug_assert!(bytes & (BYTES_IN_WORD - 1) == 0);
+    debug_assert!(src + bytes <= dst || dst + bytes <= src);
+    if bytes <= TINY_OBJECT_BYTES {
+        copy_tiny(
+            src.to_ptr::<usize>(),
+            dst.to_mut_ptr::<usize>(),
+            bytes >> LOG_BYTES_IN_WORD,
+        )
+    } else if bytes < LARGE_OBJECT_BYTES {
+        ptr::copy_nonoverlapping(src.to_ptr::<u8>(), dst.to_mut_ptr::<u8>(), bytes)
+    } else {
+        copy_large(
+            src.to_ptr::<usize>(),
+            dst.to_mut_ptr::<usize>(),
+            bytes >> LOG_BYTES_IN_WORD,
+        )
+    }
+}
+
+#[inline(always)]
+unsafe fn copy_tiny(src: *const usize, dst: *mut usize, words: usize) {
+    for i in 0..words {
+        *dst.add(i) = *src.add(i);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[inline(never)]
+unsafe fn copy_large(src: *const usize, dst: *mut usize, words: usize) {
+    use std::arch::x86_64::{_mm_sfence, _mm_stream_si64};
+    for i in 0..words {
+        _mm_stream_si64(dst.add(i) as *mut i64, *src.add(i) as i64);
+    }
+    // Non-temporal stores are weakly ordered. Make sure the copy is visible before anyone
+    // can observe the forwarding pointer to it.
+    _mm_sfence();
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+#[inline(never)]
+unsafe fn copy_large(src: *const usize, dst: *mut usize, words: usize) {
+    ptr::copy_nonoverlapping(src, dst, words)
+}
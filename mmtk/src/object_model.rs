@@ -1,16 +1,19 @@
 use std::sync::atomic::Ordering;
+use std::{mem, ptr};
 
 use super::UPCALLS;
+use crate::abi::Oop;
 use crate::{vm_metadata, OpenJDK};
+use mmtk::util::alloc::fill_alignment_gap;
 use mmtk::util::metadata::header_metadata::HeaderMetadataSpec;
 use mmtk::util::{Address, ObjectReference};
 use mmtk::vm::*;
 use mmtk::AllocationSemantics;
 use mmtk::CopyContext;
 
-pub struct VMObjectModel {}
+pub struct VMObjectModel<const HEADER_METADATA: bool> {}
 
-impl ObjectModel<OpenJDK> for VMObjectModel {
+impl<const H: bool> ObjectModel<OpenJDK<H>> for VMObjectModel<H> {
     // For now we use the default const from mmtk-core
     const GLOBAL_LOG_BIT_SPEC: VMGlobalLogBitSpec = vm_metadata::LOGGING_SIDE_METADATA_SPEC;
 
@@ -18,7 +21,7 @@ impl ObjectModel<OpenJDK> for VMObjectModel {
         vm_metadata::FORWARDING_POINTER_METADATA_SPEC;
     const LOCAL_FORWARDING_BITS_SPEC: VMLocalForwardingBitsSpec =
         vm_metadata::FORWARDING_BITS_METADATA_SPEC;
-    const LOCAL_MARK_BIT_SPEC: VMLocalMarkBitSpec = vm_metadata::MARKING_METADATA_SPEC;
+    const LOCAL_MARK_BIT_SPEC: VMLocalMarkBitSpec = vm_metadata::marking_metadata_spec(H);
     const LOCAL_LOS_MARK_NURSERY_SPEC: VMLocalLOSMarkNurserySpec = vm_metadata::LOS_METADATA_SPEC;
 
     #[inline(always)]
@@ -89,33 +92,66 @@ impl ObjectModel<OpenJDK> for VMObjectModel {
         allocator: AllocationSemantics,
         copy_context: &mut impl CopyContext,
     ) -> ObjectReference {
-        let bytes = unsafe { ((*UPCALLS).get_object_size)(from) };
+        let bytes = Self::get_current_size(from);
         let dst =
             copy_context.alloc_copy(from, bytes, ::std::mem::size_of::<usize>(), 0, allocator);
         // Copy
         let src = from.to_address();
-        for i in 0..bytes {
-            unsafe { (dst + i).store((src + i).load::<u8>()) };
-        }
+        unsafe { crate::object_copy::copy_object(src, dst, bytes) };
+        // `from` is being forwarded, so the mark word we just copied has the forwarding bits
+        // set. Clear them, so the copy keeps exactly the VM's mark state: the identity hash and
+        // age, or the placeholder of a preserved mark word, which has to follow the copy.
+        unsafe { dst.store::<usize>(dst.load::<usize>() & !vm_metadata::FORWARDING_BITS_MASK) };
         let to_obj = unsafe { dst.to_object_reference() };
+        vm_metadata::move_preserved_mark(to_obj);
         copy_context.post_copy(to_obj, unsafe { Address::zero() }, bytes, allocator);
         to_obj
     }
 
-    fn copy_to(_from: ObjectReference, _to: ObjectReference, _region: Address) -> Address {
-        unimplemented!()
+    fn copy_to(from: ObjectReference, to: ObjectReference, region: Address) -> Address {
+        let bytes = Self::get_current_size(from);
+        if from != to {
+            // A sliding compactor may move an object onto a range that overlaps its old location.
+            unsafe {
+                ptr::copy(
+                    from.to_address().to_ptr::<u8>(),
+                    to.to_address().to_mut_ptr::<u8>(),
+                    bytes,
+                )
+            };
+            vm_metadata::move_preserved_mark(to);
+        }
+        let start = Self::object_start_ref(to);
+        if !region.is_zero() {
+            fill_alignment_gap::<OpenJDK<H>>(region, start);
+        }
+        start + bytes
     }
 
-    fn get_reference_when_copied_to(_from: ObjectReference, _to: Address) -> ObjectReference {
-        unimplemented!()
+    fn get_reference_when_copied_to(_from: ObjectReference, to: Address) -> ObjectReference {
+        // OpenJDK objects start at their reference, so there is no offset to apply.
+        unsafe { to.to_object_reference() }
     }
 
+    #[inline(always)]
     fn get_current_size(object: ObjectReference) -> usize {
-        unsafe { ((*UPCALLS).get_object_size)(object) }
+        let oop: Oop = unsafe { mem::transmute(object) };
+        let size = oop.size();
+        debug_assert_eq!(
+            size,
+            unsafe { ((*UPCALLS).get_object_size)(object) },
+            "Wrong size for {:x} ({}, klass id {:?}, layout helper {:x})",
+            object,
+            oop.klass().name,
+            oop.klass().id,
+            oop.klass().layout_helper
+        );
+        size
     }
 
-    fn get_type_descriptor(_reference: ObjectReference) -> &'static [i8] {
-        unimplemented!()
+    fn get_type_descriptor(reference: ObjectReference) -> &'static [i8] {
+        let oop: Oop = unsafe { mem::transmute(reference) };
+        oop.klass().name.as_bytes()
     }
 
     fn object_start_ref(object: ObjectReference) -> Address {
@@ -1,19 +1,57 @@
 use super::abi::*;
 use super::UPCALLS;
-use crate::{OpenJDK, SINGLETON};
+use crate::gc_work::OpenJDKProcessEdges;
+use crate::options::BindingOptions;
+use crate::prefetch;
+use crate::reference_processor;
+use crate::scan_descriptor::{self, ScanDescriptor};
+use crate::scanning::create_process_edges_work;
+use crate::{singleton, OpenJDK};
 use mmtk::scheduler::ProcessEdgesWork;
-use mmtk::scheduler::{GCWorker, WorkBucketStage};
+use mmtk::scheduler::{GCWork, GCWorker, WorkBucketStage};
 use mmtk::util::constants::*;
 use mmtk::util::opaque_pointer::*;
 use mmtk::util::{Address, ObjectReference};
 use mmtk::TransitiveClosure;
+use mmtk::MMTK;
 use std::marker::PhantomData;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 use std::{mem, slice};
 
+/// Tracing of class metadata (`do_metadata` in HotSpot). A `ClassLoaderData` is alive if it
+/// is reached from a mirror, a class loader, or an instance of one of its classes. Tracing it
+/// visits the oops it holds (e.g. the mirrors of its classes).
+///
+/// Only the GC's own closure traces metadata. Other closures, like the sanity checker's, only
+/// visit the fields of the object.
+pub trait MetadataClosure {
+    fn do_cld(&mut self, cld: Address);
+}
+
+impl<T: TransitiveClosure> MetadataClosure for T {
+    #[inline(always)]
+    default fn do_cld(&mut self, _cld: Address) {}
+}
+
 trait OopIterate: Sized {
     fn oop_iterate(&self, oop: Oop, closure: &mut impl TransitiveClosure);
 }
 
+impl ScanDescriptor {
+    #[inline]
+    fn oop_iterate(&self, oop: Oop, closure: &mut impl TransitiveClosure) {
+        if self.has_no_references() {
+            return;
+        }
+        let start = oop.get_field_address(0);
+        for offset in self.offsets.iter() {
+            closure.process_edge(start + *offset as usize);
+        }
+    }
+}
+
 impl OopIterate for OopMapBlock {
     #[inline]
     fn oop_iterate(&self, oop: Oop, closure: &mut impl TransitiveClosure) {
@@ -28,9 +66,16 @@ impl OopIterate for OopMapBlock {
 impl OopIterate for InstanceKlass {
     #[inline]
     fn oop_iterate(&self, oop: Oop, closure: &mut impl TransitiveClosure) {
-        let oop_maps = self.nonstatic_oop_maps();
-        for map in oop_maps {
-            map.oop_iterate(oop, closure)
+        // A klass without reference fields has no oop maps. Its instances are leaves.
+        if self.nonstatic_oop_map_size == 0 {
+            return;
+        }
+        if use_scan_descriptors() {
+            scan_descriptor::get(self).oop_iterate(oop, closure)
+        } else {
+            for map in self.nonstatic_oop_maps() {
+                map.oop_iterate(oop, closure)
+            }
         }
     }
 }
@@ -39,32 +84,11 @@ impl OopIterate for InstanceMirrorKlass {
     #[inline]
     fn oop_iterate(&self, oop: Oop, closure: &mut impl TransitiveClosure) {
         self.instance_klass.oop_iterate(oop, closure);
-        // if (Devirtualizer::do_metadata(closure)) {
-        //     Klass* klass = java_lang_Class::as_Klass(obj);
-        //     // We'll get NULL for primitive mirrors.
-        //     if (klass != NULL) {
-        //       if (klass->is_instance_klass() && InstanceKlass::cast(klass)->is_anonymous()) {
-        //         // An anonymous class doesn't have its own class loader, so when handling
-        //         // the java mirror for an anonymous class we need to make sure its class
-        //         // loader data is claimed, this is done by calling do_cld explicitly.
-        //         // For non-anonymous classes the call to do_cld is made when the class
-        //         // loader itself is handled.
-        //         Devirtualizer::do_cld(closure, klass->class_loader_data());
-        //       } else {
-        //         Devirtualizer::do_klass(closure, klass);
-        //       }
-        //     } else {
-        //       // We would like to assert here (as below) that if klass has been NULL, then
-        //       // this has been a mirror for a primitive type that we do not need to follow
-        //       // as they are always strong roots.
-        //       // However, we might get across a klass that just changed during CMS concurrent
-        //       // marking if allocation occurred in the old generation.
-        //       // This is benign here, as we keep alive all CLDs that were loaded during the
-        //       // CMS concurrent phase in the class loading, i.e. they will be iterated over
-        //       // and kept alive during remark.
-        //       // assert(java_lang_Class::is_primitive(obj), "Sanity check");
-        //     }
-        // }
+        // A mirror keeps its class alive. For an anonymous class this is the class's own
+        // `ClassLoaderData`. A null klass is a primitive mirror, which is always strong.
+        if let Some(klass) = Self::as_klass(oop) {
+            closure.do_cld(klass.class_loader_data);
+        }
 
         // static fields
         let start: *const Oop = Self::start_of_static_fields(oop).to_ptr::<Oop>();
@@ -80,23 +104,37 @@ impl OopIterate for InstanceClassLoaderKlass {
     #[inline]
     fn oop_iterate(&self, oop: Oop, closure: &mut impl TransitiveClosure) {
         self.instance_klass.oop_iterate(oop, closure);
-        // if (Devirtualizer::do_metadata(closure)) {
-        //     ClassLoaderData* cld = java_lang_ClassLoader::loader_data(obj);
-        //     // cld can be null if we have a non-registered class loader.
-        //     if (cld != NULL) {
-        //         Devirtualizer::do_cld(closure, cld);
-        //     }
-        // }
+        // A class loader keeps all the classes it defined alive.
+        let cld = Self::loader_data(oop);
+        if !cld.is_zero() {
+            closure.do_cld(cld);
+        }
     }
 }
 
 impl OopIterate for ObjArrayKlass {
     #[inline]
     fn oop_iterate(&self, oop: Oop, closure: &mut impl TransitiveClosure) {
-        let array = unsafe { oop.as_array_oop::<Oop>() };
-        for oop in array.data() {
-            closure.process_edge(Address::from_ref(oop as &Oop));
-        }
+        oop_iterate_obj_array_range(oop, 0, obj_array_length(oop), closure)
+    }
+}
+
+#[inline]
+fn obj_array_length(oop: Oop) -> usize {
+    unsafe { oop.as_array_oop::<Oop>() }.data().len()
+}
+
+/// Process the elements `start..end` of an object array.
+#[inline]
+fn oop_iterate_obj_array_range(
+    oop: Oop,
+    start: usize,
+    end: usize,
+    closure: &mut impl TransitiveClosure,
+) {
+    let array = unsafe { oop.as_array_oop::<Oop>() };
+    for oop in &array.data()[start..end] {
+        closure.process_edge(Address::from_ref(oop as &Oop));
     }
 }
 
@@ -111,9 +149,18 @@ impl OopIterate for TypeArrayKlass {
 impl OopIterate for InstanceRefKlass {
     #[inline]
     fn oop_iterate(&self, oop: Oop, closure: &mut impl TransitiveClosure) {
-        self.instance_klass.oop_iterate(oop, closure);
-        let referent_addr = Self::referent_address(oop);
-        closure.process_edge(referent_addr);
+        let reference_type = if use_scan_descriptors() {
+            let descriptor = scan_descriptor::get(&self.instance_klass);
+            descriptor.oop_iterate(oop, closure);
+            descriptor.reference_type
+        } else {
+            self.instance_klass.oop_iterate(oop, closure);
+            self.instance_klass.reference_type()
+        };
+        if !reference_processor::discover(unsafe { mem::transmute(oop) }, reference_type) {
+            let referent_addr = Self::referent_address(oop);
+            closure.process_edge(referent_addr);
+        }
         let discovered_addr = Self::discovered_address(oop);
         closure.process_edge(discovered_addr);
     }
@@ -128,36 +175,41 @@ fn oop_iterate_slow(oop: Oop, closure: &mut impl TransitiveClosure, tls: OpaqueP
 
 #[inline]
 fn oop_iterate(oop: Oop, closure: &mut impl TransitiveClosure) {
-    let klass_id = oop.klass.id;
+    let klass_id = oop.klass().id;
     debug_assert!(
         klass_id as i32 >= 0 && (klass_id as i32) < 6,
         "Invalid klass-id: {:x} for oop: {:x}",
         klass_id as i32,
         unsafe { mem::transmute::<Oop, ObjectReference>(oop) }
     );
+    // An object keeps its class alive. Type arrays are skipped: their classes are all defined
+    // by the boot loader.
+    if klass_id != KlassID::TypeArray {
+        closure.do_cld(oop.klass().class_loader_data);
+    }
     match klass_id {
         KlassID::Instance => {
-            let instance_klass = unsafe { oop.klass.cast::<InstanceKlass>() };
+            let instance_klass = unsafe { oop.klass().cast::<InstanceKlass>() };
             instance_klass.oop_iterate(oop, closure);
         }
         KlassID::InstanceClassLoader => {
-            let instance_klass = unsafe { oop.klass.cast::<InstanceClassLoaderKlass>() };
+            let instance_klass = unsafe { oop.klass().cast::<InstanceClassLoaderKlass>() };
             instance_klass.oop_iterate(oop, closure);
         }
         KlassID::InstanceMirror => {
-            let instance_klass = unsafe { oop.klass.cast::<InstanceMirrorKlass>() };
+            let instance_klass = unsafe { oop.klass().cast::<InstanceMirrorKlass>() };
             instance_klass.oop_iterate(oop, closure);
         }
         KlassID::ObjArray => {
-            let array_klass = unsafe { oop.klass.cast::<ObjArrayKlass>() };
+            let array_klass = unsafe { oop.klass().cast::<ObjArrayKlass>() };
             array_klass.oop_iterate(oop, closure);
         }
         KlassID::TypeArray => {
-            let array_klass = unsafe { oop.klass.cast::<TypeArrayKlass>() };
+            let array_klass = unsafe { oop.klass().cast::<TypeArrayKlass>() };
             array_klass.oop_iterate(oop, closure);
         }
         KlassID::InstanceRef => {
-            let instance_klass = unsafe { oop.klass.cast::<InstanceRefKlass>() };
+            let instance_klass = unsafe { oop.klass().cast::<InstanceRefKlass>() };
             instance_klass.oop_iterate(oop, closure);
         } // _ => oop_iterate_slow(oop, closure, tls),
     }
@@ -177,25 +229,37 @@ pub fn scan_object(
     unsafe { oop_iterate(mem::transmute(object), closure) }
 }
 
-pub struct ObjectsClosure<'a, E: ProcessEdgesWork<VM = OpenJDK>>(
+pub struct ObjectsClosure<'a, E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
     Vec<Address>,
-    &'a mut GCWorker<OpenJDK>,
+    &'a mut GCWorker<OpenJDK<H>>,
     PhantomData<E>,
+    // The last `ClassLoaderData` traced. Most objects in a packet share a few classes, so this
+    // saves most of the calls into the VM.
+    Address,
 );
 
-impl<'a, E: ProcessEdgesWork<VM = OpenJDK>> TransitiveClosure for ObjectsClosure<'a, E> {
+impl<'a, E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ObjectsClosure<'a, E, H> {
+    pub fn new(worker: &'a mut GCWorker<OpenJDK<H>>) -> Self {
+        Self(Vec::new(), worker, PhantomData, Address::ZERO)
+    }
+}
+
+impl<'a, E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> TransitiveClosure
+    for ObjectsClosure<'a, E, H>
+{
     #[inline]
     fn process_edge(&mut self, slot: Address) {
-        if self.0.is_empty() {
-            self.0.reserve(E::CAPACITY);
+        if self.0.capacity() == 0 {
+            self.0 = crate::edge_buffer::take(E::CAPACITY);
         }
         self.0.push(slot);
         if self.0.len() >= E::CAPACITY {
             let mut new_edges = Vec::new();
             mem::swap(&mut new_edges, &mut self.0);
+            record_packet(new_edges.len());
             self.1.add_work(
                 WorkBucketStage::Closure,
-                E::new(new_edges, false, &SINGLETON),
+                OpenJDKProcessEdges::<E>::new(new_edges, false, singleton::<H>()),
             );
         }
     }
@@ -204,28 +268,314 @@ impl<'a, E: ProcessEdgesWork<VM = OpenJDK>> TransitiveClosure for ObjectsClosure
     }
 }
 
-impl<'a, E: ProcessEdgesWork<VM = OpenJDK>> Drop for ObjectsClosure<'a, E> {
+impl<'a, E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> MetadataClosure
+    for ObjectsClosure<'a, E, H>
+{
+    #[inline]
+    fn do_cld(&mut self, cld: Address) {
+        if cld == self.3 {
+            return;
+        }
+        self.3 = cld;
+        // The VM claims the `ClassLoaderData`, so its oops are only visited once per GC.
+        unsafe {
+            ((*UPCALLS).scan_cld)(create_process_edges_work::<E, H> as _, cld);
+        }
+    }
+}
+
+impl<'a, E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> Drop for ObjectsClosure<'a, E, H> {
     #[inline]
     fn drop(&mut self) {
         let mut new_edges = Vec::new();
         mem::swap(&mut new_edges, &mut self.0);
+        record_packet(new_edges.len());
         self.1.add_work(
             WorkBucketStage::Closure,
-            E::new(new_edges, false, &SINGLETON),
+            OpenJDKProcessEdges::<E>::new(new_edges, false, singleton::<H>()),
         );
     }
 }
 
-pub fn scan_objects_and_create_edges_work<E: ProcessEdgesWork<VM = OpenJDK>>(
-    objects: &[ObjectReference],
-    worker: &mut GCWorker<OpenJDK>,
+/// Enqueues the objects the fields point to, instead of the fields. This cuts the traffic
+/// through the work queues, but the fields are never updated, so only plans that do not move
+/// objects can use it.
+pub struct NodesClosure<'a, E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
+    Vec<ObjectReference>,
+    &'a mut GCWorker<OpenJDK<H>>,
+    PhantomData<E>,
+    // The last `ClassLoaderData` traced. See `ObjectsClosure`.
+    Address,
+);
+
+impl<'a, E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> NodesClosure<'a, E, H> {
+    pub fn new(worker: &'a mut GCWorker<OpenJDK<H>>) -> Self {
+        Self(Vec::new(), worker, PhantomData, Address::ZERO)
+    }
+
+    fn flush(&mut self) {
+        if self.0.is_empty() {
+            return;
+        }
+        let nodes = mem::take(&mut self.0);
+        record_packet(nodes.len());
+        self.1
+            .add_work(WorkBucketStage::Closure, ProcessNodes::<E, H>::new(nodes));
+    }
+}
+
+impl<'a, E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> TransitiveClosure
+    for NodesClosure<'a, E, H>
+{
+    #[inline]
+    fn process_edge(&mut self, slot: Address) {
+        let object = unsafe { slot.load::<ObjectReference>() };
+        if !object.is_null() {
+            self.process_node(object);
+        }
+    }
+    #[inline]
+    fn process_node(&mut self, object: ObjectReference) {
+        if self.0.capacity() == 0 {
+            self.0.reserve(E::CAPACITY);
+        }
+        self.0.push(object);
+        if self.0.len() >= E::CAPACITY {
+            self.flush();
+        }
+    }
+}
+
+impl<'a, E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> MetadataClosure
+    for NodesClosure<'a, E, H>
+{
+    #[inline]
+    fn do_cld(&mut self, cld: Address) {
+        if cld == self.3 {
+            return;
+        }
+        self.3 = cld;
+        // The oops of a `ClassLoaderData` are off-heap, so they are still enqueued as edges.
+        unsafe {
+            ((*UPCALLS).scan_cld)(create_process_edges_work::<E, H> as _, cld);
+        }
+    }
+}
+
+impl<'a, E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> Drop for NodesClosure<'a, E, H> {
+    #[inline]
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Traces the objects enqueued by a `NodesClosure`. The objects marked here are scanned by
+/// the `ScanObjects` packet that `E::flush` creates.
+pub struct ProcessNodes<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> {
+    nodes: Vec<ObjectReference>,
+    phantom: PhantomData<E>,
+}
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ProcessNodes<E, H> {
+    pub fn new(nodes: Vec<ObjectReference>) -> Self {
+        Self {
+            nodes,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ProcessNodes<E, H>
+{
+    fn do_work(&mut self, worker: &mut GCWorker<OpenJDK<H>>, mmtk: &'static MMTK<OpenJDK<H>>) {
+        let start = Instant::now();
+        let mut trace = E::new(vec![], false, mmtk);
+        trace.set_worker(worker);
+        prefetch::for_each(&self.nodes, prefetch::prefetch_object, |object| {
+            let new_object = trace.trace_object(object);
+            debug_assert_eq!(new_object, object, "NodesClosure used by a moving plan");
+        });
+        trace.flush();
+        record_trace_time(start);
+    }
+}
+
+static PACKETS: AtomicUsize = AtomicUsize::new(0);
+static ENTRIES: AtomicUsize = AtomicUsize::new(0);
+static TRACE_NANOS: AtomicUsize = AtomicUsize::new(0);
+
+/// Count a packet of `entries` edges or nodes created by object scanning.
+#[inline]
+fn record_packet(entries: usize) {
+    PACKETS.fetch_add(1, Ordering::Relaxed);
+    ENTRIES.fetch_add(entries, Ordering::Relaxed);
+}
+
+/// Add the time since `start` to the time spent tracing edges or nodes.
+#[inline]
+pub fn record_trace_time(start: Instant) {
+    TRACE_NANOS.fetch_add(start.elapsed().as_nanos() as usize, Ordering::Relaxed);
+}
+
+/// The traffic through the work queues since the last call: the packets object scanning
+/// created, the edges or nodes in them, and the time the workers spent tracing edges and
+/// nodes (summed over the workers). This compares the two scan modes.
+#[repr(C)]
+pub struct ScanStats {
+    pub packets: usize,
+    pub entries: usize,
+    pub trace_nanos: usize,
+}
+
+pub fn take_stats() -> ScanStats {
+    ScanStats {
+        packets: PACKETS.swap(0, Ordering::Relaxed),
+        entries: ENTRIES.swap(0, Ordering::Relaxed),
+        trace_nanos: TRACE_NANOS.swap(0, Ordering::Relaxed),
+    }
+}
+
+/// The `obj_array_chunk_size` option.
+static mut OBJ_ARRAY_CHUNK_SIZE: usize = 0;
+
+/// What object scanning enqueues: the fields themselves (`ObjectsClosure`), or the objects the
+/// fields point to (`NodesClosure`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScanMode {
+    Edges,
+    Nodes,
+}
+
+impl FromStr for ScanMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "edges" => Ok(ScanMode::Edges),
+            "nodes" => Ok(ScanMode::Nodes),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Whether object scanning enqueues the objects the fields point to (`NodesClosure`) instead of
+/// the fields themselves (`ObjectsClosure`).
+static mut ENQUEUE_NODES: bool = false;
+
+/// The `scan_descriptors` option.
+static mut USE_SCAN_DESCRIPTORS: bool = true;
+
+/// Read the `obj_array_chunk_size`, `scan_mode` and `scan_descriptors` options. This is called
+/// from `openjdk_gc_init`, once the plan is created.
+pub fn initialize(options: &BindingOptions, moves_objects: bool) {
+    unsafe { OBJ_ARRAY_CHUNK_SIZE = options.obj_array_chunk_size };
+    unsafe { USE_SCAN_DESCRIPTORS = options.scan_descriptors };
+    let enqueue_nodes = options.scan_mode == ScanMode::Nodes;
+    // A moving plan has to update the fields, so it needs the edges.
+    assert!(
+        !(enqueue_nodes && moves_objects),
+        "MMTK_SCAN_MODE=nodes is only supported by plans that do not move objects"
+    );
+    unsafe { ENQUEUE_NODES = enqueue_nodes };
+}
+
+#[inline(always)]
+fn obj_array_chunk_size() -> usize {
+    unsafe { OBJ_ARRAY_CHUNK_SIZE }
+}
+
+#[inline(always)]
+fn enqueue_nodes() -> bool {
+    unsafe { ENQUEUE_NODES }
+}
+
+#[inline(always)]
+fn use_scan_descriptors() -> bool {
+    unsafe { USE_SCAN_DESCRIPTORS }
+}
+
+/// Scans a slice of a large object array.
+pub struct ScanObjArraySlice<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> {
+    array: ObjectReference,
+    start: usize,
+    end: usize,
+    phantom: PhantomData<E>,
+}
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ScanObjArraySlice<E, H>
+{
+    fn do_work(&mut self, worker: &mut GCWorker<OpenJDK<H>>, _mmtk: &'static MMTK<OpenJDK<H>>) {
+        let oop: Oop = unsafe { mem::transmute(self.array) };
+        if enqueue_nodes() {
+            let mut closure = NodesClosure::<E, H>::new(worker);
+            oop_iterate_obj_array_range(oop, self.start, self.end, &mut closure);
+        } else {
+            let mut closure = ObjectsClosure::<E, H>::new(worker);
+            oop_iterate_obj_array_range(oop, self.start, self.end, &mut closure);
+        }
+    }
+}
+
+/// Whether an object is an object array that is split into `ScanObjArraySlice` packets.
+#[inline]
+fn is_large_obj_array(object: ObjectReference) -> bool {
+    let oop: Oop = unsafe { mem::transmute(object) };
+    oop.klass().id == KlassID::ObjArray && obj_array_length(oop) > obj_array_chunk_size()
+}
+
+fn split_obj_array<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
+    array: ObjectReference,
+    worker: &mut GCWorker<OpenJDK<H>>,
 ) {
-    let mut closure = ObjectsClosure::<E>(Vec::new(), worker, PhantomData);
-    for object in objects {
-        scan_object(
-            *object,
-            &mut closure,
-            VMWorkerThread(VMThread::UNINITIALIZED),
+    let length = obj_array_length(unsafe { mem::transmute(array) });
+    let chunk_size = obj_array_chunk_size();
+    for start in (0..length).step_by(chunk_size) {
+        worker.add_work(
+            WorkBucketStage::Closure,
+            ScanObjArraySlice::<E, H> {
+                array,
+                start,
+                end: usize::min(start + chunk_size, length),
+                phantom: PhantomData,
+            },
         );
     }
 }
+
+/// Scan the objects with the closure, except large object arrays, which are returned.
+fn scan_objects_except_large_arrays(
+    objects: &[ObjectReference],
+    closure: &mut impl TransitiveClosure,
+) -> Vec<ObjectReference> {
+    let mut large_arrays = vec![];
+    prefetch::for_each(objects, prefetch::prefetch_object, |object| {
+        if is_large_obj_array(object) {
+            // The slices only visit the elements, so the array's class is traced here. An
+            // array class belongs to the `ClassLoaderData` of its bottom class.
+            let oop: Oop = unsafe { mem::transmute(object) };
+            let array_klass = unsafe { oop.klass().cast::<ObjArrayKlass>() };
+            closure.do_cld(array_klass.bottom_klass.class_loader_data);
+            large_arrays.push(object);
+            return;
+        }
+        scan_object(object, closure, VMWorkerThread(VMThread::UNINITIALIZED));
+    });
+    large_arrays
+}
+
+pub fn scan_objects_and_create_edges_work<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
+    objects: &[ObjectReference],
+    worker: &mut GCWorker<OpenJDK<H>>,
+) {
+    // The closure is dropped, and its last packet created, before the arrays are split.
+    let large_arrays = if enqueue_nodes() {
+        scan_objects_except_large_arrays(objects, &mut NodesClosure::<E, H>::new(worker))
+    } else {
+        scan_objects_except_large_arrays(objects, &mut ObjectsClosure::<E, H>::new(worker))
+    };
+    for array in large_arrays {
+        split_obj_array::<E, H>(array, worker);
+    }
+}
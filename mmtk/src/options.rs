@@ -0,0 +1,82 @@
+//! Options of the binding.
+//!
+//! mmtk-core has a fixed set of options, so the binding keeps its own next to them, and sets
+//! them the same way: from `MMTK_<NAME>` environment variables, or with `process` (in `api`),
+//! which hands the names it does not know to mmtk-core. They are read once, at the start of
+//! `openjdk_gc_init`, so they have to be set before it.
+
+use crate::barriers::BarrierOption;
+use crate::object_scanning::ScanMode;
+use std::env;
+use std::sync::Mutex;
+
+macro_rules! options {
+    ($($(#[$doc:meta])* $name:ident: $type:ty [$validator:expr] = $default:expr),* $(,)?) => {
+        pub struct BindingOptions {
+            $($(#[$doc])* pub $name: $type),*
+        }
+
+        impl BindingOptions {
+            fn new() -> Self {
+                let mut options = Self { $($name: $default),* };
+                options.read_env_var_settings();
+                options
+            }
+
+            /// Set the option `name` to `value`. Returns `None` if the binding has no such option,
+            /// and whether `value` is valid otherwise.
+            pub fn process(&mut self, name: &str, value: &str) -> Option<bool> {
+                match name {
+                    $(stringify!($name) => Some(match value.parse::<$type>() {
+                        Ok(value) if ($validator)(&value) => {
+                            self.$name = value;
+                            true
+                        }
+                        _ => false,
+                    }),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+options! {
+    /// Keep the mark bit in the mark word, with the forwarding bits, instead of side metadata.
+    /// Without the option, it is in the mark word iff the binding is built with the
+    /// `mark_bit_in_header` feature.
+    header_metadata: bool [|_| true] = cfg!(feature = "mark_bit_in_header"),
+    /// Object arrays longer than this are scanned in slices of this many elements, each in its
+    /// own work packet. It defaults to one edge packet per slice.
+    obj_array_chunk_size: usize [|size: &usize| *size > 0] = 4096,
+    /// Whether object scanning enqueues the fields (`edges`), or the objects they point to
+    /// (`nodes`), which only plans that do not move objects support.
+    scan_mode: ScanMode [|_| true] = ScanMode::Edges,
+    /// Scan instances with the offsets cached per klass (see `scan_descriptor`), instead of
+    /// walking their oop maps. Turning it off times the cache against the oop maps.
+    scan_descriptors: bool [|_| true] = true,
+    /// How many entries ahead the scanning loops prefetch. 0 disables prefetching, which stays
+    /// the default until a distance is shown to pay off (see `HeapGraph.java`).
+    prefetch_distance: usize [|_| true] = 0,
+    /// The barrier the VM emits, if not the write barrier of the plan. See `barriers`.
+    barrier: BarrierOption [|_| true] = BarrierOption::Plan,
+}
+
+impl BindingOptions {
+    /// Like mmtk-core, take the options from the `MMTK_*` environment variables. The others are
+    /// left to mmtk-core.
+    fn read_env_var_settings(&mut self) {
+        const PREFIX: &str = "MMTK_";
+        for (key, value) in env::vars() {
+            if let Some(name) = key.strip_prefix(PREFIX) {
+                if self.process(&name.to_lowercase(), &value) == Some(false) {
+                    panic!("Invalid value for {}: {}", key, value);
+                }
+            }
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref OPTIONS: Mutex<BindingOptions> = Mutex::new(BindingOptions::new());
+}
@@ -0,0 +1,59 @@
+//! Software prefetching for the scanning loops.
+//!
+//! A GC packet walks a buffer of objects or edges, and the first touch of each object (its mark
+//! word, its klass) is usually a cache miss on a large heap. While it processes the i-th entry,
+//! a loop prefetches the object of the entry `distance()` ahead, so the miss overlaps with the
+//! work in between.
+
+use crate::options::BindingOptions;
+use mmtk::util::{Address, ObjectReference};
+use std::intrinsics;
+
+/// The `prefetch_distance` option.
+static mut PREFETCH_DISTANCE: usize = 0;
+
+/// Read the `prefetch_distance` option. This is called from `openjdk_gc_init`.
+pub fn initialize(options: &BindingOptions) {
+    unsafe { PREFETCH_DISTANCE = options.prefetch_distance };
+}
+
+#[inline(always)]
+pub fn distance() -> usize {
+    unsafe { PREFETCH_DISTANCE }
+}
+
+/// Prefetch the header of an object.
+#[inline(always)]
+pub fn prefetch_object(object: ObjectReference) {
+    // Prefetches never fault, so a bad address only wastes the hint.
+    unsafe { intrinsics::prefetch_read_data(object.to_address().to_ptr::<u8>(), 3) }
+}
+
+/// Prefetch the header of the object an edge points to. The slot itself is in an object that
+/// was scanned recently, so loading it is cheap.
+#[inline(always)]
+pub fn prefetch_edge_target(edge: Address) {
+    let object = unsafe { edge.load::<ObjectReference>() };
+    if !object.is_null() {
+        prefetch_object(object);
+    }
+}
+
+/// Prefetch the objects of `items`, `distance()` ahead of `f`, then call `f` on each item.
+#[inline(always)]
+pub fn for_each<T: Copy>(items: &[T], prefetch: impl Fn(T), mut f: impl FnMut(T)) {
+    let distance = distance();
+    if distance == 0 {
+        items.iter().for_each(|item| f(*item));
+        return;
+    }
+    for item in items.iter().take(distance) {
+        prefetch(*item);
+    }
+    for (i, item) in items.iter().enumerate() {
+        if let Some(ahead) = items.get(i + distance) {
+            prefetch(*ahead);
+        }
+        f(*item);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::reference_processor;
 use crate::OpenJDK;
 use mmtk::util::opaque_pointer::VMWorkerThread;
 use mmtk::util::ObjectReference;
@@ -6,18 +7,31 @@ use mmtk::TraceLocal;
 
 pub struct VMReferenceGlue {}
 
-impl ReferenceGlue<OpenJDK> for VMReferenceGlue {
-    fn set_referent(_reff: ObjectReference, _referent: ObjectReference) {
-        unimplemented!()
+impl<const H: bool> ReferenceGlue<OpenJDK<H>> for VMReferenceGlue {
+    fn set_referent(reff: ObjectReference, referent: ObjectReference) {
+        reference_processor::set_referent(reff, referent)
     }
-    fn get_referent(_object: ObjectReference) -> ObjectReference {
-        unimplemented!()
+    fn get_referent(object: ObjectReference) -> ObjectReference {
+        reference_processor::get_referent(object)
     }
     fn process_reference<T: TraceLocal>(
-        _trace: &mut T,
-        _reference: ObjectReference,
+        trace: &mut T,
+        reference: ObjectReference,
         _tls: VMWorkerThread,
     ) -> ObjectReference {
-        unimplemented!()
+        let referent = reference_processor::get_referent(reference);
+        if referent.is_null() {
+            return referent;
+        }
+        if trace.is_live(referent) {
+            let new_reference = trace.get_forwarded_reference(reference);
+            let new_referent = trace.get_forwarded_referent(referent);
+            reference_processor::set_referent(new_reference, new_referent);
+            new_reference
+        } else {
+            // The referent is dead: clear it, and drop the reference.
+            reference_processor::clear_referent(reference);
+            unsafe { mmtk::util::Address::ZERO.to_object_reference() }
+        }
     }
 }
@@ -0,0 +1,254 @@
+//! Processing of `java.lang.ref` references, and finalization.
+//!
+//! When reference processing is enabled (`MMTK_NO_REFERENCE_TYPES=false`), scanning a weak or
+//! phantom reference does not trace its referent. The reference is discovered instead, and
+//! handled after the strong closure. So are soft references in an emergency collection. In
+//! other collections their referents are traced like strong fields, as they would be retained
+//! anyway. Java clears weak references before it finalizes objects, and phantom references
+//! after, so the work is done in this order:
+//!
+//! * `ProcessWeakReferences` (`RefClosure`) clears every discovered soft and weak reference
+//!   whose referent was not reached. Then it finalizes the objects with a `finalize` method
+//!   that were not reached: they are traced again, so they and everything they reach stay
+//!   alive for the finalizer thread, and their closure is done in this bucket too.
+//! * `ProcessPhantomReferences` (`RefForwarding`) clears the discovered phantom references
+//!   whose referent was not reached, not even from an object to finalize.
+//!
+//! Both update the references they keep to the new location of their referents. The cleared
+//! references are linked through `Reference.discovered`, and the list is handed to the VM's
+//! pending list once the mutators are resumed. Java's `ReferenceHandler` thread then enqueues
+//! them.
+//!
+//! Final references are never discovered. The VM registers the objects with a `finalize`
+//! method when they are allocated (see `register_finalizer` in thirdPartyHeap.cpp), so it does
+//! not create `java.lang.ref.Finalizer` objects, and any other `FinalReference` is treated as
+//! strong. mmtk-core finalizes objects in `RefClosure` too, where it cannot be ordered with
+//! weak references, so the binding turns it off and finalizes the objects itself.
+
+use crate::abi::{InstanceRefKlass, ReferenceType};
+use crate::{OpenJDK, UPCALLS};
+use mmtk::scheduler::{GCWork, GCWorker, ProcessEdgesWork};
+use mmtk::util::{Address, ObjectReference};
+use mmtk::MMTK;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static FINALIZATION_ENABLED: AtomicBool = AtomicBool::new(false);
+/// Whether soft references are discovered in this GC, i.e. whether it is an emergency collection.
+static DISCOVER_SOFT: AtomicBool = AtomicBool::new(false);
+
+/// Discovered references, by strength. Only references that were reached in this GC, and
+/// whose referent was not null when they were scanned, are in the lists.
+#[derive(Default)]
+struct DiscoveredLists {
+    soft: Mutex<Vec<ObjectReference>>,
+    weak: Mutex<Vec<ObjectReference>>,
+    phantom: Mutex<Vec<ObjectReference>>,
+}
+
+lazy_static! {
+    static ref DISCOVERED: DiscoveredLists = DiscoveredLists::default();
+    /// The head and the tail of the references cleared in the last GC.
+    static ref PENDING: Mutex<Option<(ObjectReference, ObjectReference)>> = Mutex::new(None);
+    /// The objects with a `finalize` method that were reachable at the end of the last GC, or
+    /// allocated since.
+    static ref FINALIZABLE: Mutex<Vec<ObjectReference>> = Mutex::new(vec![]);
+    /// The objects to finalize, until the finalizer thread takes them.
+    static ref READY_FOR_FINALIZATION: Mutex<Vec<ObjectReference>> = Mutex::new(vec![]);
+}
+
+/// Set from `openjdk_gc_init`, once MMTk's options are known.
+pub fn initialize(enabled: bool, finalization_enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+    FINALIZATION_ENABLED.store(finalization_enabled, Ordering::SeqCst);
+}
+
+/// Whether this GC discovers soft references. This is called from `scan_vm_specific_roots`,
+/// before any object is scanned.
+pub fn prepare(emergency: bool) {
+    DISCOVER_SOFT.store(emergency, Ordering::SeqCst);
+}
+
+#[inline(always)]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn is_finalization_enabled() -> bool {
+    FINALIZATION_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Try to discover a reference while scanning it. Returns false if the referent should be
+/// traced as a strong edge instead.
+#[inline]
+pub fn discover(reference: ObjectReference, reference_type: ReferenceType) -> bool {
+    if !is_enabled() {
+        return false;
+    }
+    let list = match reference_type {
+        ReferenceType::Soft if DISCOVER_SOFT.load(Ordering::Relaxed) => &DISCOVERED.soft,
+        ReferenceType::Weak => &DISCOVERED.weak,
+        ReferenceType::Phantom => &DISCOVERED.phantom,
+        _ => return false,
+    };
+    if get_referent(reference).is_null() {
+        return false;
+    }
+    list.lock().unwrap().push(reference);
+    true
+}
+
+pub fn get_referent(reference: ObjectReference) -> ObjectReference {
+    InstanceRefKlass::referent(unsafe { mem::transmute(reference) })
+}
+
+pub fn set_referent(reference: ObjectReference, referent: ObjectReference) {
+    InstanceRefKlass::set_referent(unsafe { mem::transmute(reference) }, referent)
+}
+
+fn set_discovered(reference: ObjectReference, discovered: ObjectReference) {
+    InstanceRefKlass::set_discovered(unsafe { mem::transmute(reference) }, discovered)
+}
+
+pub fn clear_referent(reference: ObjectReference) {
+    set_referent(reference, unsafe { Address::ZERO.to_object_reference() })
+}
+
+/// Register an object with a `finalize` method. It is finalized once it is no longer reachable.
+pub fn add_finalizer(object: ObjectReference) {
+    if is_finalization_enabled() {
+        FINALIZABLE.lock().unwrap().push(object);
+    }
+}
+
+/// The next object the finalizer thread should finalize, if any.
+pub fn get_finalized_object() -> Option<ObjectReference> {
+    READY_FOR_FINALIZATION.lock().unwrap().pop()
+}
+
+/// Hand the references cleared in the last GC to the VM. This is called after the mutators
+/// are resumed, because publishing the list takes the `Heap_lock`, which a Java thread may
+/// hold while it is stopped for the GC.
+pub fn enqueue_pending_references() {
+    if let Some((head, tail)) = PENDING.lock().unwrap().take() {
+        unsafe {
+            ((*UPCALLS).enqueue_references)(head, tail);
+        }
+    }
+}
+
+/// Clear the references of `references` whose referent was not reached, and update the others.
+/// The cleared references are pushed to the front of `cleared`.
+fn process_discovered<E: ProcessEdgesWork>(
+    trace: &mut E,
+    references: Vec<ObjectReference>,
+    cleared: &mut Option<(ObjectReference, ObjectReference)>,
+) {
+    for reference in references {
+        let referent = get_referent(reference);
+        if referent.is_live() {
+            // Tracing a live object only returns its new location.
+            set_referent(reference, trace.trace_object(referent));
+        } else {
+            clear_referent(reference);
+            // The VM links the tail to its current pending list.
+            *cleared = match *cleared {
+                Some((head, tail)) => {
+                    set_discovered(reference, head);
+                    Some((reference, tail))
+                }
+                None => Some((reference, reference)),
+            };
+        }
+    }
+}
+
+/// Add the references cleared by a packet to the pending list of this GC.
+fn add_pending(cleared: Option<(ObjectReference, ObjectReference)>) {
+    if let Some((head, tail)) = cleared {
+        let mut pending = PENDING.lock().unwrap();
+        *pending = match *pending {
+            Some((pending_head, pending_tail)) => {
+                set_discovered(tail, pending_head);
+                Some((head, pending_tail))
+            }
+            None => Some((head, tail)),
+        };
+    }
+}
+
+pub struct ProcessWeakReferences<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
+    PhantomData<E>,
+);
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ProcessWeakReferences<E, H> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ProcessWeakReferences<E, H>
+{
+    fn do_work(&mut self, worker: &mut GCWorker<OpenJDK<H>>, mmtk: &'static MMTK<OpenJDK<H>>) {
+        let mut trace = E::new(vec![], false, mmtk);
+        trace.set_worker(worker);
+        let mut cleared = None;
+        for list in &[&DISCOVERED.soft, &DISCOVERED.weak] {
+            let references = mem::take(&mut *list.lock().unwrap());
+            process_discovered(&mut trace, references, &mut cleared);
+        }
+        debug_assert!(trace.nodes.is_empty());
+        add_pending(cleared);
+
+        // Only now that the weak references to them are cleared, keep the objects to finalize
+        // alive.
+        let found = {
+            let mut ready = READY_FOR_FINALIZATION.lock().unwrap();
+            for object in ready.iter_mut() {
+                *object = trace.trace_object(*object);
+            }
+            let waiting = ready.len();
+            let mut finalizable = FINALIZABLE.lock().unwrap();
+            for object in mem::take(&mut *finalizable) {
+                if object.is_live() {
+                    finalizable.push(trace.trace_object(object));
+                } else {
+                    ready.push(trace.trace_object(object));
+                }
+            }
+            ready.len() > waiting
+        };
+        trace.flush();
+        if found {
+            unsafe { ((*UPCALLS).schedule_finalizer)() };
+        }
+    }
+}
+
+pub struct ProcessPhantomReferences<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool>(
+    PhantomData<E>,
+);
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> ProcessPhantomReferences<E, H> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork<VM = OpenJDK<H>>, const H: bool> GCWork<OpenJDK<H>>
+    for ProcessPhantomReferences<E, H>
+{
+    fn do_work(&mut self, worker: &mut GCWorker<OpenJDK<H>>, mmtk: &'static MMTK<OpenJDK<H>>) {
+        let mut trace = E::new(vec![], false, mmtk);
+        trace.set_worker(worker);
+        let mut cleared = None;
+        let references = mem::take(&mut *DISCOVERED.phantom.lock().unwrap());
+        process_discovered(&mut trace, references, &mut cleared);
+        debug_assert!(trace.nodes.is_empty());
+        add_pending(cleared);
+    }
+}
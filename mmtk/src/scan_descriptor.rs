@@ -0,0 +1,103 @@
+//! Cached scan descriptors of instance klasses.
+//!
+//! Scanning an instance through its `InstanceKlass` finds the oop maps behind the vtable and
+//! the itable, and walks them block by block. A `ScanDescriptor` flattens the oop maps of a
+//! klass into the offsets of its reference fields, once per klass.
+//!
+//! Each GC thread has its own cache, so lookups never contend. All the caches are dropped
+//! when the VM invalidates them, which it does after unloading classes (a new klass may be
+//! allocated where a dead one was) and when classes may have been redefined.
+//!
+//! Klasses without reference fields never get here: object scanning skips their instances
+//! before the lookup. The `scan_descriptors` option scans through the oop maps instead, to
+//! time the cache against them.
+
+use crate::abi::{InstanceKlass, ReferenceType};
+use mmtk::util::constants::*;
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How to scan the instances of a klass.
+pub struct ScanDescriptor {
+    /// The offsets of the reference fields, in bytes from the start of the object.
+    pub offsets: Box<[u32]>,
+    pub reference_type: ReferenceType,
+}
+
+impl ScanDescriptor {
+    fn new(klass: &InstanceKlass) -> Self {
+        let mut offsets = vec![];
+        for map in klass.nonstatic_oop_maps() {
+            for i in 0..map.count as u32 {
+                offsets.push(map.offset as u32 + (i << LOG_BYTES_IN_ADDRESS));
+            }
+        }
+        Self {
+            offsets: offsets.into_boxed_slice(),
+            reference_type: klass.reference_type(),
+        }
+    }
+
+    /// Whether the instances have no reference fields, so their fields need no scanning.
+    #[inline(always)]
+    pub fn has_no_references(&self) -> bool {
+        self.offsets.is_empty()
+    }
+}
+
+/// The keys are klass addresses, which need no more than a multiplicative hash.
+#[derive(Default)]
+struct KlassHasher(u64);
+
+impl Hasher for KlassHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, _bytes: &[u8]) {
+        unreachable!()
+    }
+    #[inline(always)]
+    fn write_usize(&mut self, klass: usize) {
+        self.0 = (klass as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    }
+}
+
+/// The descriptors are boxed, so they stay where they are when the map grows.
+type Cache = HashMap<usize, Box<ScanDescriptor>, BuildHasherDefault<KlassHasher>>;
+
+static EPOCH: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The descriptors of this thread, and the epoch they were created in. Only `get` touches
+    /// it, and `get` does not call itself, so no borrow is ever outstanding.
+    static CACHE: UnsafeCell<(usize, Cache)> = UnsafeCell::new((0, Cache::default()));
+}
+
+/// The scan descriptor of a klass.
+///
+/// The descriptor is dropped in the first lookup of this thread after `invalidate`, so it must
+/// not be kept across calls to `get`. Object scanning only holds it while scanning one object.
+#[inline]
+pub fn get(klass: &InstanceKlass) -> &ScanDescriptor {
+    let epoch = EPOCH.load(Ordering::Relaxed);
+    let descriptor: *const ScanDescriptor = CACHE.with(|cache| {
+        let cache = unsafe { &mut *cache.get() };
+        if cache.0 != epoch {
+            cache.0 = epoch;
+            cache.1.clear();
+        }
+        &**cache
+            .1
+            .entry(klass as *const InstanceKlass as usize)
+            .or_insert_with(|| Box::new(ScanDescriptor::new(klass))) as *const _
+    });
+    unsafe { &*descriptor }
+}
+
+/// Drop the descriptors of all threads. The VM calls this in a pause, when no instance of a
+/// dead or redefined klass is left to scan.
+pub fn invalidate() {
+    EPOCH.fetch_add(1, Ordering::SeqCst);
+}
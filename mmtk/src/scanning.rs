@@ -1,9 +1,12 @@
 use super::gc_work::*;
-use super::{NewBuffer, SINGLETON, UPCALLS};
+use super::{singleton, NewBuffer, UPCALLS};
+use crate::field_barrier::ProcessLoggedSlots;
+use crate::object_barrier::ProcessLoggedNodes;
+use crate::reference_processor::{ProcessPhantomReferences, ProcessWeakReferences};
 use crate::OpenJDK;
 use mmtk::memory_manager;
 use mmtk::scheduler::ProcessEdgesWork;
-use mmtk::scheduler::{GCWorker, WorkBucketStage};
+use mmtk::scheduler::{GCWork, GCWorker, WorkBucketStage};
 use mmtk::util::opaque_pointer::*;
 use mmtk::util::{Address, ObjectReference};
 use mmtk::vm::Scanning;
@@ -12,7 +15,10 @@ use mmtk::{Mutator, TransitiveClosure};
 
 pub struct VMScanning {}
 
-pub(crate) extern "C" fn create_process_edges_work<W: ProcessEdgesWork<VM = OpenJDK>>(
+pub(crate) extern "C" fn create_process_edges_work<
+    W: ProcessEdgesWork<VM = OpenJDK<H>>,
+    const H: bool,
+>(
     ptr: *mut Address,
     length: usize,
     capacity: usize,
@@ -20,16 +26,16 @@ pub(crate) extern "C" fn create_process_edges_work<W: ProcessEdgesWork<VM = Open
     if !ptr.is_null() {
         let buf = unsafe { Vec::<Address>::from_raw_parts(ptr, length, capacity) };
         memory_manager::add_work_packet(
-            &SINGLETON,
+            singleton::<H>(),
             WorkBucketStage::Closure,
-            W::new(buf, false, &SINGLETON),
+            OpenJDKProcessEdges::<W>::new(buf, false, singleton::<H>()),
         );
     }
-    let (ptr, _, capacity) = Vec::with_capacity(W::CAPACITY).into_raw_parts();
+    let (ptr, _, capacity) = crate::edge_buffer::take(W::CAPACITY).into_raw_parts();
     NewBuffer { ptr, capacity }
 }
 
-impl Scanning<OpenJDK> for VMScanning {
+impl<const H: bool> Scanning<OpenJDK<H>> for VMScanning {
     const SCAN_MUTATORS_IN_SAFEPOINT: bool = false;
     const SINGLE_THREAD_MUTATOR_SCANNING: bool = false;
 
@@ -46,54 +52,103 @@ impl Scanning<OpenJDK> for VMScanning {
         // TODO
     }
 
-    fn scan_objects<W: ProcessEdgesWork<VM = OpenJDK>>(
+    fn scan_objects<W: ProcessEdgesWork<VM = OpenJDK<H>>>(
         objects: &[ObjectReference],
-        worker: &mut GCWorker<OpenJDK>,
+        worker: &mut GCWorker<OpenJDK<H>>,
     ) {
-        crate::object_scanning::scan_objects_and_create_edges_work::<W>(&objects, worker);
+        crate::object_scanning::scan_objects_and_create_edges_work::<W, H>(&objects, worker);
     }
 
-    fn scan_thread_roots<W: ProcessEdgesWork<VM = OpenJDK>>() {
-        let process_edges = create_process_edges_work::<W>;
+    fn scan_thread_roots<W: ProcessEdgesWork<VM = OpenJDK<H>>>() {
+        let process_edges = create_process_edges_work::<W, H>;
         unsafe {
             ((*UPCALLS).scan_thread_roots)(process_edges as _);
         }
     }
 
-    fn scan_thread_root<W: ProcessEdgesWork<VM = OpenJDK>>(
-        mutator: &'static mut Mutator<OpenJDK>,
+    fn scan_thread_root<W: ProcessEdgesWork<VM = OpenJDK<H>>>(
+        mutator: &'static mut Mutator<OpenJDK<H>>,
         _tls: VMWorkerThread,
     ) {
         let tls = mutator.get_tls();
-        let process_edges = create_process_edges_work::<W>;
+        let process_edges = create_process_edges_work::<W, H>;
         unsafe {
             ((*UPCALLS).scan_thread_root)(process_edges as _, tls);
         }
     }
 
-    fn scan_vm_specific_roots<W: ProcessEdgesWork<VM = OpenJDK>>() {
-        memory_manager::add_work_packets(
-            &SINGLETON,
-            WorkBucketStage::Prepare,
-            vec![
-                box ScanUniverseRoots::<W>::new(),
-                box ScanJNIHandlesRoots::<W>::new(),
-                box ScanObjectSynchronizerRoots::<W>::new(),
-                box ScanManagementRoots::<W>::new(),
-                box ScanJvmtiExportRoots::<W>::new(),
-                box ScanAOTLoaderRoots::<W>::new(),
-                box ScanSystemDictionaryRoots::<W>::new(),
-                box ScanCodeCacheRoots::<W>::new(),
-                box ScanStringTableRoots::<W>::new(),
-                box ScanClassLoaderDataGraphRoots::<W>::new(),
-                box ScanWeakProcessorRoots::<W>::new(),
-            ],
-        );
-        if !(Self::SCAN_MUTATORS_IN_SAFEPOINT && Self::SINGLE_THREAD_MUTATOR_SCANNING) {
+    fn scan_vm_specific_roots<W: ProcessEdgesWork<VM = OpenJDK<H>>>() {
+        crate::reference_processor::prepare(singleton::<H>().get_plan().is_emergency_collection());
+        // The string table and the weak processor roots are only strong in nursery GCs.
+        let full_heap = is_full_heap_gc(singleton::<H>());
+        let mut packets: Vec<Box<dyn GCWork<OpenJDK<H>>>> = vec![
+            box ScanUniverseRoots::<W, H>::new(),
+            box ScanJNIHandlesRoots::<W, H>::new(),
+            box ScanObjectSynchronizerRoots::<W, H>::new(),
+            box ScanManagementRoots::<W, H>::new(),
+            box ScanJvmtiExportRoots::<W, H>::new(),
+            box ScanAOTLoaderRoots::<W, H>::new(),
+            box ScanSystemDictionaryRoots::<W, H>::new(),
+            // The code cache is scanned under `CodeCache_lock`, so one packet scans all of it.
+            box ScanCodeCacheRoots::<W, H>::new(),
+        ];
+        if !full_heap {
+            packets.push(box ScanWeakProcessorRoots::<W, H>::new());
+        }
+        // The string table and the CLD graph are scanned by one packet per worker. The VM
+        // claims string table blocks and CLDs, so each is scanned once.
+        let workers = singleton::<H>().options.threads;
+        for _ in 0..workers {
+            if !full_heap {
+                packets.push(box ScanStringTableRoots::<W, H>::new());
+            }
+            packets.push(box ScanClassLoaderDataGraphRoots::<W, H>::new());
+        }
+        memory_manager::add_work_packets(singleton::<H>(), WorkBucketStage::Prepare, packets);
+        if !(<Self as Scanning<OpenJDK<H>>>::SCAN_MUTATORS_IN_SAFEPOINT
+            && <Self as Scanning<OpenJDK<H>>>::SINGLE_THREAD_MUTATOR_SCANNING)
+        {
+            memory_manager::add_work_packet(
+                singleton::<H>(),
+                WorkBucketStage::Prepare,
+                ScanVMThreadRoots::<W, H>::new(),
+            );
+        }
+        if crate::object_barrier::logs_nodes() {
+            memory_manager::add_work_packet(
+                singleton::<H>(),
+                WorkBucketStage::Prepare,
+                ProcessLoggedNodes::<W, H>::new(),
+            );
+        }
+        if crate::field_barrier::logs_slots() {
             memory_manager::add_work_packet(
-                &SINGLETON,
+                singleton::<H>(),
                 WorkBucketStage::Prepare,
-                ScanVMThreadRoots::<W>::new(),
+                ProcessLoggedSlots::<W, H>::new(),
+            );
+        }
+        if full_heap {
+            memory_manager::add_work_packet(
+                singleton::<H>(),
+                WorkBucketStage::RefForwarding,
+                ProcessWeakRoots::<W, H>::new(),
+            );
+        }
+        if crate::reference_processor::is_enabled()
+            || crate::reference_processor::is_finalization_enabled()
+        {
+            memory_manager::add_work_packet(
+                singleton::<H>(),
+                WorkBucketStage::RefClosure,
+                ProcessWeakReferences::<W, H>::new(),
+            );
+        }
+        if crate::reference_processor::is_enabled() {
+            memory_manager::add_work_packet(
+                singleton::<H>(),
+                WorkBucketStage::RefForwarding,
+                ProcessPhantomReferences::<W, H>::new(),
             );
         }
     }
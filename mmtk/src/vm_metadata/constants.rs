@@ -1,3 +1,5 @@
+use crate::OpenJDK;
+use mmtk::util::alloc::MarkCompactAllocator;
 use mmtk::vm::*;
 
 #[cfg(target_pointer_width = "64")]
@@ -5,8 +7,24 @@ pub(crate) const FORWARDING_BITS_OFFSET: isize = 56;
 #[cfg(target_pointer_width = "32")]
 pub(crate) const FORWARDING_BITS_OFFSET: isize = 0;
 
+/// The bits of the mark word that hold the forwarding state. They are unused in an unlocked
+/// header, but they belong to a pointer in a biased or locked one, which is preserved before
+/// they are set (see `preserved_marks`). Everything else in the mark word belongs to the VM.
+pub(crate) const FORWARDING_BITS_MASK: usize = 0b11 << FORWARDING_BITS_OFFSET;
+
+/// The forwarding pointer replaces the whole mark word of the old copy. This is fine, since it
+/// is only written after the object (mark word included) has been copied, and nothing reads
+/// the header of a from-space object after that.
 pub(crate) const FORWARDING_POINTER_OFFSET: isize = 0;
 
+/// MarkCompact computes the new address of every live object before it moves any of them,
+/// so it cannot keep forwarding pointers in the mark word like the copying plans do (the
+/// mark word still has to be intact when the object is moved). Its allocator instead
+/// reserves this many bytes in front of each object, and the forwarding pointer is stored
+/// there. The fastpaths in the VM need to know about this gap.
+pub(crate) const MARK_COMPACT_HEADER_RESERVED_IN_BYTES: usize =
+    MarkCompactAllocator::<OpenJDK<false>>::HEADER_RESERVED_IN_BYTES;
+
 // Global MetadataSpecs - Start
 
 /// Global logging bit metadata spec
@@ -24,21 +42,29 @@ pub(crate) const FORWARDING_POINTER_METADATA_SPEC: VMLocalForwardingPointerSpec
 
 /// PolicySpecific object forwarding status metadata spec
 /// 2 bits per object
+///
+/// They are in the mark word in both layouts, as they always were.
 pub(crate) const FORWARDING_BITS_METADATA_SPEC: VMLocalForwardingBitsSpec =
     VMLocalForwardingBitsSpec::in_header(FORWARDING_BITS_OFFSET);
 
 /// PolicySpecific mark bit metadata spec
 /// 1 bit per object
-#[cfg(feature = "mark_bit_in_header")]
-pub(crate) const MARKING_METADATA_SPEC: VMLocalMarkBitSpec =
-    VMLocalMarkBitSpec::in_header(FORWARDING_BITS_OFFSET);
-
-#[cfg(not(feature = "mark_bit_in_header"))]
-pub(crate) const MARKING_METADATA_SPEC: VMLocalMarkBitSpec =
-    VMLocalMarkBitSpec::side_after(LOS_METADATA_SPEC.as_spec());
+///
+/// In the header, the mark bit shares the forwarding bits, as no policy uses both.
+pub(crate) const fn marking_metadata_spec(header: bool) -> VMLocalMarkBitSpec {
+    if header {
+        VMLocalMarkBitSpec::in_header(FORWARDING_BITS_OFFSET)
+    } else {
+        VMLocalMarkBitSpec::side_after(LOS_METADATA_SPEC.as_spec())
+    }
+}
 
 /// PolicySpecific mark-and-nursery bits metadata spec
 /// 2-bits per object
+///
+/// Unlike the mark bit and the forwarding bits, these bits must survive between GCs. They
+/// always live on the side: the mark word of a stack-locked object is replaced by a pointer
+/// to its displaced header, which would drop any bits we keep there.
 pub(crate) const LOS_METADATA_SPEC: VMLocalLOSMarkNurserySpec =
     VMLocalLOSMarkNurserySpec::side_first();
 
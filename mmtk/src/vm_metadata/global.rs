@@ -1,3 +1,4 @@
+use super::preserve_mark;
 use mmtk::util::{metadata as mmtk_meta, ObjectReference};
 use std::sync::atomic::Ordering;
 
@@ -21,6 +22,9 @@ pub(crate) fn store_metadata(
     optional_mask: Option<usize>,
     atomic_ordering: Option<Ordering>,
 ) {
+    if val != 0 {
+        preserve_mark(metadata_spec, object);
+    }
     mmtk_meta::header_metadata::store_metadata(
         metadata_spec,
         object,
@@ -41,6 +45,9 @@ pub(crate) fn compare_exchange_metadata(
     success_order: Ordering,
     failure_order: Ordering,
 ) -> bool {
+    if new_metadata != 0 {
+        preserve_mark(metadata_spec, object);
+    }
     mmtk_meta::header_metadata::compare_exchange_metadata(
         metadata_spec,
         object,
@@ -60,6 +67,9 @@ pub(crate) fn fetch_add_metadata(
     val: usize,
     order: Ordering,
 ) -> usize {
+    if val != 0 {
+        preserve_mark(metadata_spec, object);
+    }
     mmtk_meta::header_metadata::fetch_add_metadata(metadata_spec, object, val, order)
 }
 
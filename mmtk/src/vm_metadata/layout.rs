@@ -0,0 +1,24 @@
+//! Runtime selection of the metadata layout.
+//!
+//! The mark bit either lives in the mark word, with the forwarding bits (the header layout), or
+//! in side metadata (the side layout). The forwarding bits are in the mark word in
+//! both layouts, and the other bits are always on the side. mmtk-core takes the metadata specs as associated
+//! constants of the `ObjectModel`, so the binding is instantiated once per layout
+//! (`OpenJDK<true>` and `OpenJDK<false>`), and every entry point dispatches on the layout
+//! selected here.
+
+use crate::options::BindingOptions;
+
+static mut HEADER_METADATA: bool = cfg!(feature = "mark_bit_in_header");
+
+/// Select the layout with the `header_metadata` option. This is called at the start of
+/// `openjdk_gc_init`, before anything uses the MMTk instance.
+pub fn initialize(options: &BindingOptions) {
+    unsafe { HEADER_METADATA = options.header_metadata };
+}
+
+/// Whether the header layout is active.
+#[inline(always)]
+pub fn header_metadata() -> bool {
+    unsafe { HEADER_METADATA }
+}
@@ -1,5 +1,9 @@
 mod constants;
 mod global;
+mod layout;
+mod preserved_marks;
 
 pub(crate) use self::constants::*;
 pub(crate) use self::global::*;
+pub(crate) use self::layout::*;
+pub(crate) use self::preserved_marks::*;
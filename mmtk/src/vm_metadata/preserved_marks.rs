@@ -0,0 +1,112 @@
+//! Mark words preserved while MMTk uses their top bits.
+//!
+//! The forwarding bits, and in the header layout the mark bit, are the top bits of the mark
+//! word (`FORWARDING_BITS_OFFSET`). In an unlocked header HotSpot leaves them unused, but a
+//! biased, stack-locked or inflated header holds a pointer there (to the owning thread, the
+//! displaced header or the `ObjectMonitor`). Before MMTk sets its bits in such a header, the
+//! mark word is saved here and replaced by a placeholder with the `marked` lock pattern, which
+//! HotSpot only uses inside its own collectors. The placeholder keeps the index of the saved
+//! mark word, so a copy of the object can be found again. The saved mark words are written
+//! back at the end of the GC, before the mutators resume.
+
+use super::{FORWARDING_BITS_MASK, FORWARDING_BITS_OFFSET};
+use mmtk::util::metadata::header_metadata::HeaderMetadataSpec;
+use mmtk::util::ObjectReference;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// The lock patterns of markOopDesc.
+const LOCK_MASK: usize = 0b11;
+const UNLOCKED_VALUE: usize = 0b01;
+const MARKED_VALUE: usize = 0b11;
+const BIASED_LOCK_BIT: usize = 0b100;
+/// The owner of a biased header starts after the lock, age and epoch bits.
+const BIASED_THREAD_SHIFT: usize = 10;
+/// The index of a preserved mark word is kept above the lock and age bits of the placeholder,
+/// and below the MMTk bits.
+const INDEX_SHIFT: usize = 8;
+
+lazy_static! {
+    /// The preserved objects, with their mark words. The object of an entry follows its copies.
+    static ref PRESERVED_MARKS: Mutex<Vec<(ObjectReference, usize)>> = Mutex::new(vec![]);
+}
+
+#[inline(always)]
+fn mark_word(object: ObjectReference) -> &'static AtomicUsize {
+    unsafe { &*object.to_address().to_ptr::<AtomicUsize>() }
+}
+
+/// Whether the mark word holds a pointer in the bits MMTk uses.
+#[inline(always)]
+fn must_be_preserved(mark: usize) -> bool {
+    match mark & LOCK_MASK {
+        UNLOCKED_VALUE => {
+            mark & BIASED_LOCK_BIT != 0
+                && (mark & !FORWARDING_BITS_MASK) >> BIASED_THREAD_SHIFT != 0
+        }
+        MARKED_VALUE => false,
+        _ => true,
+    }
+}
+
+/// Called before MMTk sets bits of `spec` in the header of `object`. If the header holds a
+/// pointer and none of the MMTk bits is set yet, replace it with a placeholder.
+#[inline(always)]
+pub(crate) fn preserve_mark(spec: &HeaderMetadataSpec, object: ObjectReference) {
+    if spec.bit_offset != FORWARDING_BITS_OFFSET {
+        // The forwarding pointer, which only replaces the mark word of an old copy.
+        return;
+    }
+    let mark = mark_word(object).load(Ordering::SeqCst);
+    if mark & FORWARDING_BITS_MASK == 0 && must_be_preserved(mark) {
+        preserve_mark_slow(object);
+    }
+}
+
+#[cold]
+fn preserve_mark_slow(object: ObjectReference) {
+    let mut marks = PRESERVED_MARKS.lock().unwrap();
+    loop {
+        let mark = mark_word(object).load(Ordering::SeqCst);
+        if mark & FORWARDING_BITS_MASK != 0 || !must_be_preserved(mark) {
+            // Another GC thread got there first.
+            return;
+        }
+        let index = marks.len();
+        debug_assert!(index < 1 << (FORWARDING_BITS_OFFSET as usize - INDEX_SHIFT));
+        let placeholder = (index << INDEX_SHIFT) | MARKED_VALUE;
+        if mark_word(object)
+            .compare_exchange(mark, placeholder, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+        {
+            marks.push((object, mark));
+            return;
+        }
+    }
+}
+
+/// Called after `from` was copied to `to`, mark word included. The preserved mark word of
+/// `from`, if any, now belongs to `to`.
+#[inline(always)]
+pub(crate) fn move_preserved_mark(to: ObjectReference) {
+    let mark = mark_word(to).load(Ordering::Relaxed);
+    if mark & LOCK_MASK == MARKED_VALUE {
+        let index = (mark & !FORWARDING_BITS_MASK) >> INDEX_SHIFT;
+        PRESERVED_MARKS.lock().unwrap()[index].0 = to;
+    }
+}
+
+/// Write the preserved mark words back. This is called at the end of each GC, after MMTk is
+/// done with its bits, and before the mutators resume.
+pub(crate) fn restore_preserved_marks() {
+    let mut marks = PRESERVED_MARKS.lock().unwrap();
+    for (index, (object, mark)) in marks.drain(..).enumerate() {
+        debug_assert_eq!(
+            (mark_word(object).load(Ordering::Relaxed) & !FORWARDING_BITS_MASK) >> INDEX_SHIFT,
+            index,
+            "Lost the placeholder of {}",
+            object
+        );
+        mark_word(object).store(mark, Ordering::Relaxed);
+    }
+}
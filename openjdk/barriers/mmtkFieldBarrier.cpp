@@ -0,0 +1,90 @@
+#include "mmtkFieldBarrier.hpp"
+#include "runtime/interfaceSupport.inline.hpp"
+
+void MMTkFieldBarrierSetRuntime::record_modified_edge_slow(void* slot) {
+  if (log_slot(slot)) {
+    Thread::current()->third_party_heap_mutator.record_modified_edge(slot);
+  }
+}
+
+void MMTkFieldBarrierSetRuntime::record_modified_edge(oop src, void* slot) {
+  if (is_unlogged((void*) src)) {
+    record_modified_edge_slow(slot);
+  }
+}
+
+#define __ masm->
+
+void MMTkFieldBarrierSetAssembler::oop_store_at(MacroAssembler* masm, DecoratorSet decorators, BasicType type, Address dst, Register val, Register tmp1, Register tmp2) {
+  bool in_heap = (decorators & IN_HEAP) != 0;
+  assert((decorators & IS_DEST_UNINITIALIZED) == 0, "unsupported");
+
+  // Storing null never creates an old-to-young pointer.
+  if (!in_heap || val == noreg) {
+    BarrierSetAssembler::store_at(masm, decorators, type, dst, val, tmp1, tmp2);
+    return;
+  }
+
+  BarrierSetAssembler::store_at(masm, decorators, type, dst, val, tmp1, tmp2);
+
+  record_modified_edge(masm, dst, tmp1, tmp2);
+}
+
+void MMTkFieldBarrierSetAssembler::record_modified_edge(MacroAssembler* masm, Address dst, Register tmp1, Register tmp2) {
+  Label done;
+
+  jump_if_logged(masm, dst.base(), tmp2, done);
+
+  __ lea(c_rarg0, dst);
+  __ call_VM_leaf_base(CAST_FROM_FN_PTR(address, MMTkFieldBarrierSetRuntime::record_modified_edge_slow), 1);
+
+  __ bind(done);
+}
+
+#undef __
+
+#ifdef ASSERT
+#define __ gen->lir(__FILE__, __LINE__)->
+#else
+#define __ gen->lir()->
+#endif
+
+void MMTkFieldBarrierSetC1::record_modified_node(LIRAccess& access, LIR_Opr src, LIR_Opr slot, LIR_Opr new_val) {
+  LIRGenerator* gen = access.gen();
+  DecoratorSet decorators = access.decorators();
+  if ((decorators & IN_HEAP) == 0) return;
+  load_operands(gen, src, slot, new_val);
+  CodeStub* slow = new MMTkFieldBarrierStub(slot);
+
+  branch_if_unlogged(gen, src, slow);
+
+  __ branch_destination(slow->continuation());
+}
+
+#undef __
+
+#define __ ideal.
+
+void MMTkFieldBarrierSetC2::record_modified_edge(GraphKit* kit, Node* src, Node* slot, Node* val) const {
+  if (val != NULL && val->is_Con()) {
+    const Type* t = val->bottom_type();
+    if (t == TypePtr::NULL_PTR) return;
+  }
+
+  MMTkIdealKit ideal(kit, true);
+
+  float unlikely  = PROB_UNLIKELY(0.999);
+  Node* zero  = __ ConI(0);
+  Node* result = unlogged_bit(ideal, src);
+
+  __ if_then(result, BoolTest::ne, zero, unlikely); {
+      // The slot is passed as a raw address, so it is not a derived oop.
+      Node* raw_slot = __ CastXP(__ CastPX(__ ctrl(), slot));
+      const TypeFunc* tf = __ func_type(TypeRawPtr::BOTTOM);
+      Node* x = __ make_leaf_call(tf, CAST_FROM_FN_PTR(address, MMTkFieldBarrierSetRuntime::record_modified_edge_slow), "record_modified_edge", raw_slot);
+  } __ end_if();
+
+  kit->final_sync(ideal); // Final sync IdealKit and GraphKit.
+}
+
+#undef __
@@ -0,0 +1,139 @@
+#ifndef MMTK_BARRIERS_FIELD_BARRIER
+#define MMTK_BARRIERS_FIELD_BARRIER
+
+#include "mmtkObjectBarrier.hpp"
+
+// The field barrier logs the slots written in objects whose unlogged bit is set (mature
+// objects), instead of the objects. It takes the fast path of the object barrier, but leaves
+// the bit of the object set. The slow path sets the log bit of the slot instead, and only logs
+// slots whose bit was clear. MMTk clears the bits at the next GC. Array copies log the copied
+// slots, and clones still log the whole object.
+
+class MMTkFieldBarrierSetRuntime: public MMTkObjectBarrierSetRuntime {
+public:
+  static void record_modified_edge_slow(void* slot);
+
+  virtual bool is_slow_path_call(address call) {
+    return call == CAST_FROM_FN_PTR(address, record_modified_edge_slow) || MMTkObjectBarrierSetRuntime::is_slow_path_call(call);
+  }
+
+  virtual void record_modified_edge(oop src, void* slot);
+};
+
+class MMTkFieldBarrierStub;
+
+class MMTkFieldBarrierSetAssembler: public MMTkObjectBarrierSetAssembler {
+  void oop_store_at(MacroAssembler* masm, DecoratorSet decorators, BasicType type, Address dst, Register val, Register tmp1, Register tmp2);
+  void record_modified_edge(MacroAssembler* masm, Address dst, Register tmp1, Register tmp2);
+public:
+  virtual void store_at(MacroAssembler* masm, DecoratorSet decorators, BasicType type, Address dst, Register val, Register tmp1, Register tmp2) {
+    if (type == T_OBJECT || type == T_ARRAY) {
+      oop_store_at(masm, decorators, type, dst, val, tmp1, tmp2);
+    } else {
+      BarrierSetAssembler::store_at(masm, decorators, type, dst, val, tmp1, tmp2);
+    }
+  }
+  inline void gen_field_barrier_stub(LIR_Assembler* ce, MMTkFieldBarrierStub* stub);
+#define __ sasm->
+  void generate_c1_field_barrier_runtime_stub(StubAssembler* sasm) {
+    __ prologue("mmtk_field_barrier", false);
+
+    __ push(c_rarg0);
+    __ push(rax);
+
+    __ load_parameter(0, c_rarg0);
+
+    __ save_live_registers_no_oop_map(true);
+
+    __ call_VM_leaf_base(CAST_FROM_FN_PTR(address, MMTkFieldBarrierSetRuntime::record_modified_edge_slow), 1);
+
+    __ restore_live_registers(true);
+
+    __ pop(rax);
+    __ pop(c_rarg0);
+
+    __ epilogue();
+  }
+#undef __
+};
+
+struct MMTkFieldBarrierStub: CodeStub {
+  LIR_Opr _slot;
+  MMTkFieldBarrierStub(LIR_Opr slot): _slot(slot) {}
+  virtual void emit_code(LIR_Assembler* ce) {
+    MMTkFieldBarrierSetAssembler* bs = (MMTkFieldBarrierSetAssembler*) BarrierSet::barrier_set()->barrier_set_assembler();
+    bs->gen_field_barrier_stub(ce, this);
+  }
+  virtual void visit(LIR_OpVisitState* visitor) {
+    visitor->do_slow_case();
+    if (_slot != NULL) visitor->do_input(_slot);
+  }
+  NOT_PRODUCT(virtual void print_name(outputStream* out) const { out->print("MMTkFieldBarrierStub"); });
+};
+
+class MMTkFieldBarrierSetC1: public MMTkObjectBarrierSetC1 {
+public:
+  class MMTkFieldBarrierCodeGenClosure : public StubAssemblerCodeGenClosure {
+    virtual OopMapSet* generate_code(StubAssembler* sasm) {
+      MMTkFieldBarrierSetAssembler* bs = (MMTkFieldBarrierSetAssembler*) BarrierSet::barrier_set()->barrier_set_assembler();
+      bs->generate_c1_field_barrier_runtime_stub(sasm);
+      return NULL;
+    }
+  };
+  virtual void record_modified_node(LIRAccess& access, LIR_Opr src, LIR_Opr slot, LIR_Opr new_val);
+  CodeBlob* _field_barrier_c1_runtime_code_blob;
+  virtual void generate_c1_runtime_stubs(BufferBlob* buffer_blob) {
+    MMTkObjectBarrierSetC1::generate_c1_runtime_stubs(buffer_blob);
+    MMTkFieldBarrierCodeGenClosure field_code_gen_cl;
+    _field_barrier_c1_runtime_code_blob = Runtime1::generate_blob(buffer_blob, -1, "field_code_gen_cl", false, &field_code_gen_cl);
+  }
+};
+
+class MMTkFieldBarrierSetC2: public MMTkObjectBarrierSetC2 {
+  void record_modified_edge(GraphKit* kit, Node* src, Node* slot, Node* val) const;
+public:
+  virtual Node* store_at_resolved(C2Access& access, C2AccessValue& val) const {
+    Node* store = BarrierSetC2::store_at_resolved(access, val);
+    if (access.is_oop()) record_modified_edge(access.kit(), access.base(), access.addr().node(), val.node());
+    return store;
+  }
+  virtual Node* atomic_cmpxchg_val_at_resolved(C2AtomicAccess& access, Node* expected_val, Node* new_val, const Type* value_type) const {
+    Node* result = BarrierSetC2::atomic_cmpxchg_val_at_resolved(access, expected_val, new_val, value_type);
+    if (access.is_oop()) record_modified_edge(access.kit(), access.base(), access.addr().node(), new_val);
+    return result;
+  }
+  virtual Node* atomic_cmpxchg_bool_at_resolved(C2AtomicAccess& access, Node* expected_val, Node* new_val, const Type* value_type) const {
+    Node* load_store = BarrierSetC2::atomic_cmpxchg_bool_at_resolved(access, expected_val, new_val, value_type);
+    if (access.is_oop()) record_modified_edge(access.kit(), access.base(), access.addr().node(), new_val);
+    return load_store;
+  }
+  virtual Node* atomic_xchg_at_resolved(C2AtomicAccess& access, Node* new_val, const Type* value_type) const {
+    Node* result = BarrierSetC2::atomic_xchg_at_resolved(access, new_val, value_type);
+    if (access.is_oop()) record_modified_edge(access.kit(), access.base(), access.addr().node(), new_val);
+    return result;
+  }
+  virtual bool is_gc_barrier_node(Node* node) const {
+    if (node->Opcode() != Op_CallLeaf) return false;
+    CallLeafNode *call = node->as_CallLeaf();
+    return call->_name != NULL && (strcmp(call->_name, "record_modified_edge") == 0 || strcmp(call->_name, "record_modified_node") == 0);
+  }
+};
+
+#define __ ce->masm()->
+inline void MMTkFieldBarrierSetAssembler::gen_field_barrier_stub(LIR_Assembler* ce, MMTkFieldBarrierStub* stub) {
+  MMTkFieldBarrierSetC1* bs = (MMTkFieldBarrierSetC1*) BarrierSet::barrier_set()->barrier_set_c1();
+  __ bind(*stub->entry());
+  ce->store_parameter(stub->_slot->as_pointer_register(), 0);
+  __ call(RuntimeAddress(bs->_field_barrier_c1_runtime_code_blob->code_begin()));
+  __ jmp(*stub->continuation());
+}
+#undef __
+
+struct MMTkFieldBarrier: MMTkBarrierImpl<
+  MMTkFieldBarrierSetRuntime,
+  MMTkFieldBarrierSetAssembler,
+  MMTkFieldBarrierSetC1,
+  MMTkFieldBarrierSetC2
+> {};
+
+#endif
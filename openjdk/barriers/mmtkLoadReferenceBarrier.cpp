@@ -0,0 +1,122 @@
+#include "mmtkLoadReferenceBarrier.hpp"
+#include "runtime/interfaceSupport.inline.hpp"
+
+oopDesc* MMTkLoadReferenceBarrierSetRuntime::load_reference_slow(oopDesc* obj) {
+  return (oopDesc*) MMTkBarrierSet::descriptor->load_reference_slow((void*) obj);
+}
+
+bool MMTkLoadReferenceBarrierSetRuntime::may_be_forwarded(oopDesc* obj) {
+  return (((uintptr_t) obj->mark_raw()) & MMTkBarrierSet::descriptor->forwarding_bits_mask) != 0;
+}
+
+oop MMTkLoadReferenceBarrierSetRuntime::load_reference(oop object) {
+  if (object == NULL || !may_be_forwarded((oopDesc*) object)) return object;
+  return oop(load_reference_slow((oopDesc*) object));
+}
+
+#define __ masm->
+
+void MMTkLoadReferenceBarrierSetAssembler::jump_if_not_forwarded(MacroAssembler* masm, Register obj, Label& done) {
+  const MMTkBarrierDescriptor* barrier = MMTkBarrierSet::descriptor;
+  // Test the byte of the mark word that holds the forwarding bits.
+  size_t byte_in_word = barrier->forwarding_bits_shift / BitsPerByte;
+  assert((barrier->forwarding_bits_mask >> (byte_in_word * BitsPerByte)) <= 0xff, "the forwarding bits must be in one byte");
+  int byte_offset = oopDesc::mark_offset_in_bytes() + (int) byte_in_word;
+  __ testb(Address(obj, byte_offset), (int) (barrier->forwarding_bits_mask >> (byte_in_word * BitsPerByte)));
+  __ jcc(Assembler::zero, done);
+}
+
+void MMTkLoadReferenceBarrierSetAssembler::load_reference_barrier(MacroAssembler* masm, Register dst) {
+  Label done;
+
+  __ testptr(dst, dst);
+  __ jcc(Assembler::zero, done);
+
+  jump_if_not_forwarded(masm, dst, done);
+
+  // The callers do not expect any register but dst to change.
+  __ pusha();
+  __ mov(c_rarg0, dst);
+  __ call_VM_leaf_base(CAST_FROM_FN_PTR(address, MMTkLoadReferenceBarrierSetRuntime::load_reference_slow), 1);
+  // Overwrite the saved dst, so that popa restores the resolved reference into it. pusha saves
+  // rax at the highest address and r15 at the lowest, in the order of the register encodings.
+  __ movptr(Address(rsp, (15 - dst->encoding()) * wordSize), rax);
+  __ popa();
+
+  __ bind(done);
+}
+
+#undef __
+
+#ifdef ASSERT
+#define __ gen->lir(__FILE__, __LINE__)->
+#else
+#define __ gen->lir()->
+#endif
+
+void MMTkLoadReferenceBarrierSetC1::load_at_resolved(LIRAccess& access, LIR_Opr result) {
+  if (!access.is_oop() || (access.decorators() & AS_RAW) != 0) {
+    BarrierSetC1::load_at_resolved(access, result);
+    return;
+  }
+  LIRGenerator* gen = access.gen();
+  LIR_Opr obj = gen->new_register(T_OBJECT);
+  BarrierSetC1::load_at_resolved(access, obj);
+  __ move(obj, result);
+  // The forwarding bits are tested in the stub, which calls the runtime if they are set.
+  CodeStub* slow = new MMTkLoadReferenceBarrierStub(obj, result);
+  __ cmp(lir_cond_notEqual, obj, LIR_OprFact::oopConst(NULL));
+  __ branch(lir_cond_notEqual, T_OBJECT, slow);
+  __ branch_destination(slow->continuation());
+}
+
+#undef __
+
+#define __ ideal.
+
+const TypeFunc* load_reference_slow_Type() {
+  const Type **fields = TypeTuple::fields(1);
+  fields[TypeFunc::Parms+0] = TypeOopPtr::BOTTOM; // oop obj
+  const TypeTuple *domain = TypeTuple::make(TypeFunc::Parms+1, fields);
+  fields = TypeTuple::fields(1);
+  fields[TypeFunc::Parms+0] = TypeOopPtr::BOTTOM; // the current copy of obj
+  const TypeTuple *range = TypeTuple::make(TypeFunc::Parms+1, fields);
+  return TypeFunc::make(domain, range);
+}
+
+Node* MMTkLoadReferenceBarrierSetC2::forwarding_bits(MMTkIdealKit& ideal, Node* obj) {
+  const MMTkBarrierDescriptor* barrier = MMTkBarrierSet::descriptor;
+  Node* no_base = __ top();
+  Node* addr = __ CastPX(__ ctrl(), obj);
+  size_t byte_in_word = barrier->forwarding_bits_shift / BitsPerByte;
+  int byte_offset = oopDesc::mark_offset_in_bytes() + (int) byte_in_word;
+  Node* mark_addr = __ AddP(no_base, __ CastXP(addr), __ ConX(byte_offset));
+  Node* byte = __ load(__ ctrl(), mark_addr, TypeInt::INT, T_BYTE, Compile::AliasIdxRaw);
+  return __ AndI(byte, __ ConI((jint) (barrier->forwarding_bits_mask >> (byte_in_word * BitsPerByte))));
+}
+
+Node* MMTkLoadReferenceBarrierSetC2::load_reference_barrier(GraphKit* kit, Node* obj) const {
+  if (obj->bottom_type() == TypePtr::NULL_PTR) return obj;
+
+  MMTkIdealKit ideal(kit, true, true);
+  IdealVariable result(ideal);
+  __ declarations_done();
+  __ set(result, obj);
+
+  float unlikely  = PROB_UNLIKELY(0.999);
+  Node* zero  = __ ConI(0);
+
+  __ if_then(obj, BoolTest::ne, kit->null()); {
+    __ if_then(forwarding_bits(ideal, obj), BoolTest::ne, zero, unlikely); {
+      Node* resolved = __ make_leaf_call(load_reference_slow_Type(), CAST_FROM_FN_PTR(address, MMTkLoadReferenceBarrierSetRuntime::load_reference_slow), "mmtk_load_reference_slow", obj);
+      __ set(result, resolved);
+    } __ end_if();
+  } __ end_if();
+
+  Node* resolved = __ value(result);
+  kit->final_sync(ideal); // Final sync IdealKit and GraphKit.
+  // The merged value only has the type of an oop.
+  return kit->gvn().transform(new CheckCastPPNode(kit->control(), resolved, obj->bottom_type()));
+}
+
+#undef __
@@ -0,0 +1,139 @@
+#ifndef MMTK_BARRIERS_LOAD_REFERENCE_BARRIER
+#define MMTK_BARRIERS_LOAD_REFERENCE_BARRIER
+
+#include "opto/callnode.hpp"
+#include "opto/idealKit.hpp"
+#include "c1/c1_LIRAssembler.hpp"
+#include "c1/c1_MacroAssembler.hpp"
+#include "../mmtkBarrierSet.hpp"
+#include "../mmtkBarrierSetAssembler_x86.hpp"
+#include "../mmtkBarrierSetC1.hpp"
+#include "../mmtkBarrierSetC2.hpp"
+
+// The load reference barrier resolves every reference loaded from the heap or a root to the
+// current copy of the object, for plans that copy objects while the mutators run. An object is
+// forwarded if its forwarding bits are set, and the forwarding pointer is then its mark word.
+// The forwarding bits are in the mark word with both metadata layouts. It has no write barrier.
+
+class MMTkLoadReferenceBarrierSetRuntime: public MMTkBarrierSetRuntime {
+public:
+  static oopDesc* load_reference_slow(oopDesc* obj);
+  // Whether the forwarding bits of the object are set.
+  static bool may_be_forwarded(oopDesc* obj);
+
+  virtual bool is_slow_path_call(address call) {
+    return call == CAST_FROM_FN_PTR(address, load_reference_slow);
+  }
+
+  virtual oop load_reference(oop object);
+};
+
+class MMTkLoadReferenceBarrierStub;
+
+class MMTkLoadReferenceBarrierSetAssembler: public MMTkBarrierSetAssembler {
+  void load_reference_barrier(MacroAssembler* masm, Register dst);
+  // Jump to `done` if the object is not forwarded, like may_be_forwarded.
+  void jump_if_not_forwarded(MacroAssembler* masm, Register obj, Label& done);
+public:
+  virtual void load_at(MacroAssembler* masm, DecoratorSet decorators, BasicType type, Register dst, Address src, Register tmp1, Register tmp_thread) {
+    BarrierSetAssembler::load_at(masm, decorators, type, dst, src, tmp1, tmp_thread);
+    if ((type == T_OBJECT || type == T_ARRAY) && (decorators & AS_RAW) == 0) {
+      load_reference_barrier(masm, dst);
+    }
+  }
+  inline void gen_load_reference_barrier_stub(LIR_Assembler* ce, MMTkLoadReferenceBarrierStub* stub);
+#define __ sasm->
+  void generate_c1_load_reference_barrier_runtime_stub(StubAssembler* sasm) {
+    __ prologue("mmtk_load_reference_barrier", false);
+
+    __ save_live_registers_no_oop_map(true);
+
+    __ load_parameter(0, c_rarg0);
+
+    __ call_VM_leaf_base(CAST_FROM_FN_PTR(address, MMTkLoadReferenceBarrierSetRuntime::load_reference_slow), 1);
+
+    // Return the resolved reference in place of the parameter, so that no register changes.
+    __ movptr(Address(rbp, 2 * BytesPerWord), rax);
+
+    __ restore_live_registers(true);
+
+    __ epilogue();
+  }
+#undef __
+};
+
+// The slow path of C1: obj is not null, and result already holds it.
+struct MMTkLoadReferenceBarrierStub: CodeStub {
+  LIR_Opr _obj, _result;
+  MMTkLoadReferenceBarrierStub(LIR_Opr obj, LIR_Opr result): _obj(obj), _result(result) {}
+  virtual void emit_code(LIR_Assembler* ce) {
+    MMTkLoadReferenceBarrierSetAssembler* bs = (MMTkLoadReferenceBarrierSetAssembler*) BarrierSet::barrier_set()->barrier_set_assembler();
+    bs->gen_load_reference_barrier_stub(ce, this);
+  }
+  virtual void visit(LIR_OpVisitState* visitor) {
+    visitor->do_slow_case();
+    visitor->do_input(_obj);
+    visitor->do_temp(_obj);
+    visitor->do_temp(_result);
+  }
+  NOT_PRODUCT(virtual void print_name(outputStream* out) const { out->print("MMTkLoadReferenceBarrierStub"); });
+};
+
+class MMTkLoadReferenceBarrierSetC1: public MMTkBarrierSetC1 {
+public:
+  class MMTkLoadReferenceBarrierCodeGenClosure : public StubAssemblerCodeGenClosure {
+    virtual OopMapSet* generate_code(StubAssembler* sasm) {
+      MMTkLoadReferenceBarrierSetAssembler* bs = (MMTkLoadReferenceBarrierSetAssembler*) BarrierSet::barrier_set()->barrier_set_assembler();
+      bs->generate_c1_load_reference_barrier_runtime_stub(sasm);
+      return NULL;
+    }
+  };
+  CodeBlob* _load_reference_barrier_c1_runtime_code_blob;
+  virtual void load_at_resolved(LIRAccess& access, LIR_Opr result);
+  virtual void generate_c1_runtime_stubs(BufferBlob* buffer_blob) {
+    MMTkLoadReferenceBarrierCodeGenClosure load_reference_code_gen_cl;
+    _load_reference_barrier_c1_runtime_code_blob = Runtime1::generate_blob(buffer_blob, -1, "load_reference_code_gen_cl", false, &load_reference_code_gen_cl);
+  }
+};
+
+class MMTkLoadReferenceBarrierSetC2: public MMTkBarrierSetC2 {
+  Node* load_reference_barrier(GraphKit* kit, Node* obj) const;
+  // The forwarding bits of obj, as an int.
+  static Node* forwarding_bits(MMTkIdealKit& ideal, Node* obj);
+public:
+  virtual Node* load_at_resolved(C2Access& access, const Type* val_type) const {
+    Node* load = BarrierSetC2::load_at_resolved(access, val_type);
+    if (access.is_oop() && (access.decorators() & AS_RAW) == 0) {
+      load = load_reference_barrier(access.kit(), load);
+    }
+    return load;
+  }
+  virtual bool is_gc_barrier_node(Node* node) const {
+    if (node->Opcode() != Op_CallLeaf) return false;
+    CallLeafNode *call = node->as_CallLeaf();
+    return call->_name != NULL && strcmp(call->_name, "mmtk_load_reference_slow") == 0;
+  }
+};
+
+#define __ ce->masm()->
+inline void MMTkLoadReferenceBarrierSetAssembler::gen_load_reference_barrier_stub(LIR_Assembler* ce, MMTkLoadReferenceBarrierStub* stub) {
+  MMTkLoadReferenceBarrierSetC1* bs = (MMTkLoadReferenceBarrierSetC1*) BarrierSet::barrier_set()->barrier_set_c1();
+  Register obj = stub->_obj->as_register();
+  Register result = stub->_result->as_register();
+  __ bind(*stub->entry());
+  jump_if_not_forwarded(ce->masm(), obj, *stub->continuation());
+  ce->store_parameter(obj, 0);
+  __ call(RuntimeAddress(bs->_load_reference_barrier_c1_runtime_code_blob->code_begin()));
+  __ movptr(result, Address(rsp, 0));
+  __ jmp(*stub->continuation());
+}
+#undef __
+
+struct MMTkLoadReferenceBarrier: MMTkBarrierImpl<
+  MMTkLoadReferenceBarrierSetRuntime,
+  MMTkLoadReferenceBarrierSetAssembler,
+  MMTkLoadReferenceBarrierSetC1,
+  MMTkLoadReferenceBarrierSetC2
+> {};
+
+#endif
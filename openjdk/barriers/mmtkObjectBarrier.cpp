@@ -1,17 +1,53 @@
 #include "mmtkObjectBarrier.hpp"
+#include "runtime/atomic.hpp"
 #include "runtime/interfaceSupport.inline.hpp"
 
 void MMTkObjectBarrierSetRuntime::record_modified_node_slow(void* obj) {
-  ::record_modified_node((MMTk_Mutator) &Thread::current()->third_party_heap_mutator, (void*) obj);
+  if (log_object(obj)) {
+    Thread::current()->third_party_heap_mutator.record_modified_node(obj);
+  }
+}
+
+bool MMTkObjectBarrierSetRuntime::is_unlogged(void* obj) {
+    const MMTkBarrierDescriptor* barrier = MMTkBarrierSet::descriptor;
+    uintptr_t addr = (uintptr_t) obj;
+    uint8_t* meta_addr = (uint8_t*) (barrier->log_bit_base + (addr >> barrier->log_bit_byte_shift));
+    uintptr_t shift = (addr >> barrier->log_bit_shift) & barrier->log_bit_mask;
+    uint8_t byte_val = *meta_addr;
+    return ((byte_val >> shift) & 1) == 1;
+}
+
+bool MMTkObjectBarrierSetRuntime::log_object(void* obj) {
+    const MMTkBarrierDescriptor* barrier = MMTkBarrierSet::descriptor;
+    uintptr_t addr = (uintptr_t) obj;
+    volatile uint8_t* meta_addr = (volatile uint8_t*) (barrier->log_bit_base + (addr >> barrier->log_bit_byte_shift));
+    uint8_t bit = (uint8_t) (1 << ((addr >> barrier->log_bit_shift) & barrier->log_bit_mask));
+    uint8_t old_val = *meta_addr;
+    while ((old_val & bit) != 0) {
+        uint8_t cur_val = Atomic::cmpxchg((uint8_t) (old_val & ~bit), meta_addr, old_val);
+        if (cur_val == old_val) return true;
+        old_val = cur_val;
+    }
+    return false;
+}
+
+bool MMTkObjectBarrierSetRuntime::log_slot(void* slot) {
+    const MMTkBarrierDescriptor* barrier = MMTkBarrierSet::descriptor;
+    uintptr_t addr = (uintptr_t) slot;
+    volatile uint8_t* meta_addr = (volatile uint8_t*) (barrier->slot_log_base + (addr >> barrier->slot_log_byte_shift));
+    uint8_t bit = (uint8_t) (1 << ((addr >> barrier->slot_log_shift) & barrier->log_bit_mask));
+    uint8_t old_val = *meta_addr;
+    while ((old_val & bit) == 0) {
+        uint8_t cur_val = Atomic::cmpxchg((uint8_t) (old_val | bit), meta_addr, old_val);
+        if (cur_val == old_val) return true;
+        old_val = cur_val;
+    }
+    return false;
 }
 
 void MMTkObjectBarrierSetRuntime::record_modified_node(oop src) {
 #if MMTK_ENABLE_OBJECT_BARRIER_FASTPATH
-    intptr_t addr = (intptr_t) (void*) src;
-    uint8_t* meta_addr = (uint8_t*) (SIDE_METADATA_BASE_ADDRESS + (addr >> 6));
-    intptr_t shift = (addr >> 3) & 0b111;
-    uint8_t byte_val = *meta_addr;
-    if (((byte_val >> shift) & 1) == 1) {
+    if (is_unlogged((void*) src)) {
       record_modified_node_slow((void*) src);
     }
 #else
@@ -19,8 +55,70 @@ void MMTkObjectBarrierSetRuntime::record_modified_node(oop src) {
 #endif
 }
 
+void MMTkObjectBarrierSetRuntime::array_copy_post_slow(void* dst_slots, size_t count) {
+  // The stubs do not know the arrays.
+  MMTkBarrierSet::descriptor->array_copy_post((MMTk_Mutator) &Thread::current()->third_party_heap_mutator, NULL, NULL, dst_slots, count);
+}
+
+// MMTk logs the array, or the copied slots, itself.
+void MMTkObjectBarrierSetRuntime::array_copy_pre(oop src, oop dst, void* dst_slots, size_t count) {
+  MMTkBarrierSet::descriptor->array_copy_pre((MMTk_Mutator) &Thread::current()->third_party_heap_mutator, (void*) src, (void*) dst, dst_slots, count);
+}
+
+void MMTkObjectBarrierSetRuntime::array_copy_post(oop src, oop dst, void* dst_slots, size_t count) {
+  MMTkBarrierSet::descriptor->array_copy_post((MMTk_Mutator) &Thread::current()->third_party_heap_mutator, (void*) src, (void*) dst, dst_slots, count);
+}
+
 #define __ masm->
 
+void MMTkObjectBarrierSetAssembler::arraycopy_prologue(MacroAssembler* masm, DecoratorSet decorators, BasicType type, Register src, Register dst, Register count) {
+  if (type != T_OBJECT && type != T_ARRAY) return;
+  // An uninitialized destination was just allocated, so it is young, and needs no barrier.
+  if ((decorators & IS_DEST_UNINITIALIZED) != 0) return;
+  bool checkcast = (decorators & ARRAYCOPY_CHECKCAST) != 0;
+  bool disjoint = (decorators & ARRAYCOPY_DISJOINT) != 0;
+  bool obj_int = type == T_OBJECT && UseCompressedOops;
+  // The copy loops consume count, or dst for disjoint narrow copies. Save them for the
+  // epilogue, like the card table barrier does. The checkcast stub keeps both.
+  if (!checkcast) {
+    if (!obj_int) {
+      __ movptr(r11, count);
+    } else if (disjoint) {
+      __ movq(r11, dst);
+    }
+  }
+}
+
+void MMTkObjectBarrierSetAssembler::arraycopy_epilogue(MacroAssembler* masm, DecoratorSet decorators, BasicType type, Register src, Register dst, Register count) {
+  if (type != T_OBJECT && type != T_ARRAY) return;
+  if ((decorators & IS_DEST_UNINITIALIZED) != 0) return;
+  bool checkcast = (decorators & ARRAYCOPY_CHECKCAST) != 0;
+  bool disjoint = (decorators & ARRAYCOPY_DISJOINT) != 0;
+  bool obj_int = type == T_OBJECT && UseCompressedOops;
+  if (!checkcast) {
+    if (!obj_int) {
+      count = r11;
+    } else if (disjoint) {
+      dst = r11;
+    }
+  }
+  Label done;
+  __ testptr(count, count);
+  __ jcc(Assembler::zero, done);
+
+  // The stubs only get the slots, not the arrays, so MMTk logs the copied slots (see
+  // array_copy_post in the binding).
+  __ pusha();
+  assert_different_registers(count, rscratch1);
+  __ movptr(rscratch1, dst);
+  __ movptr(c_rarg1, count);
+  __ movptr(c_rarg0, rscratch1);
+  __ call_VM_leaf_base(CAST_FROM_FN_PTR(address, MMTkObjectBarrierSetRuntime::array_copy_post_slow), 2);
+  __ popa();
+
+  __ bind(done);
+}
+
 void MMTkObjectBarrierSetAssembler::oop_store_at(MacroAssembler* masm, DecoratorSet decorators, BasicType type, Address dst, Register val, Register tmp1, Register tmp2) {
   bool in_heap = (decorators & IN_HEAP) != 0;
   bool as_normal = (decorators & AS_NORMAL) != 0;
@@ -36,33 +134,38 @@ void MMTkObjectBarrierSetAssembler::oop_store_at(MacroAssembler* masm, Decorator
   record_modified_node(masm, dst.base(), tmp1, tmp2);
 }
 
-void MMTkObjectBarrierSetAssembler::record_modified_node(MacroAssembler* masm, Register obj, Register tmp1, Register tmp2) {
-#if MMTK_ENABLE_OBJECT_BARRIER_FASTPATH
-  Label done;
-
+void MMTkObjectBarrierSetAssembler::jump_if_logged(MacroAssembler* masm, Register obj, Register tmp, Label& logged) {
   Register tmp3 = rscratch1;
   Register tmp4 = rscratch2;
-  assert_different_registers(obj, tmp2, tmp3);
+  assert_different_registers(obj, tmp, tmp3);
   assert_different_registers(tmp4, rcx);
+  const MMTkBarrierDescriptor* barrier = MMTkBarrierSet::descriptor;
 
-  // tmp2 = load-byte (SIDE_METADATA_BASE_ADDRESS + (obj >> 6));
+  // tmp = load-byte (log_bit_base + (obj >> log_bit_byte_shift));
   __ movptr(tmp3, obj);
-  __ shrptr(tmp3, 6);
-  __ movptr(tmp2, SIDE_METADATA_BASE_ADDRESS);
-  __ movb(tmp2, Address(tmp2, tmp3));
-  // tmp3 = (obj >> 3) & 7
+  __ shrptr(tmp3, (int) barrier->log_bit_byte_shift);
+  __ movptr(tmp, (intptr_t) barrier->log_bit_base);
+  __ movb(tmp, Address(tmp, tmp3));
+  // tmp3 = (obj >> log_bit_shift) & log_bit_mask
   __ movptr(tmp3, obj);
-  __ shrptr(tmp3, 3);
-  __ andptr(tmp3, 7);
-  // tmp2 = tmp2 >> tmp3
+  __ shrptr(tmp3, (int) barrier->log_bit_shift);
+  __ andptr(tmp3, (int32_t) barrier->log_bit_mask);
+  // tmp = tmp >> tmp3
   __ movptr(tmp4, rcx);
   __ movl(rcx, tmp3);
-  __ shrptr(tmp2);
+  __ shrptr(tmp);
   __ movptr(rcx, tmp4);
-  // if ((tmp2 & 1) == 1) goto slowpath;
-  __ andptr(tmp2, 1);
-  __ cmpptr(tmp2, 1);
-  __ jcc(Assembler::notEqual, done);
+  // if ((tmp & 1) != 1) goto logged;
+  __ andptr(tmp, 1);
+  __ cmpptr(tmp, 1);
+  __ jcc(Assembler::notEqual, logged);
+}
+
+void MMTkObjectBarrierSetAssembler::record_modified_node(MacroAssembler* masm, Register obj, Register tmp1, Register tmp2) {
+#if MMTK_ENABLE_OBJECT_BARRIER_FASTPATH
+  Label done;
+
+  jump_if_logged(masm, obj, tmp2, done);
 
   assert_different_registers(c_rarg0, obj);
   __ movptr(c_rarg0, obj);
@@ -84,10 +187,7 @@ void MMTkObjectBarrierSetAssembler::record_modified_node(MacroAssembler* masm, R
 #define __ gen->lir()->
 #endif
 
-void MMTkObjectBarrierSetC1::record_modified_node(LIRAccess& access, LIR_Opr src, LIR_Opr slot, LIR_Opr new_val) {
-  LIRGenerator* gen = access.gen();
-  DecoratorSet decorators = access.decorators();
-  if ((decorators & IN_HEAP) == 0) return;
+void MMTkObjectBarrierSetC1::load_operands(LIRGenerator* gen, LIR_Opr& src, LIR_Opr& slot, LIR_Opr& new_val) {
   if (!src->is_register()) {
     LIR_Opr reg = gen->new_pointer_register();
     if (src->is_constant()) {
@@ -118,22 +218,23 @@ void MMTkObjectBarrierSetC1::record_modified_node(LIRAccess& access, LIR_Opr src
     new_val = new_val_reg;
   }
   assert(new_val->is_register(), "must be a register at this point");
-  CodeStub* slow = new MMTkObjectBarrierStub(src, slot, new_val);
+}
 
-#if MMTK_ENABLE_OBJECT_BARRIER_FASTPATH
+void MMTkObjectBarrierSetC1::branch_if_unlogged(LIRGenerator* gen, LIR_Opr src, CodeStub* slow) {
+  const MMTkBarrierDescriptor* barrier = MMTkBarrierSet::descriptor;
   LIR_Opr addr = src;
-  // uint8_t* meta_addr = (uint8_t*) (SIDE_METADATA_BASE_ADDRESS + (addr >> 6));
+  // uint8_t* meta_addr = (uint8_t*) (log_bit_base + (addr >> log_bit_byte_shift));
   LIR_Opr offset = gen->new_pointer_register();
   __ move(addr, offset);
-  __ shift_right(offset, 6, offset);
+  __ shift_right(offset, (int) barrier->log_bit_byte_shift, offset);
   LIR_Opr base = gen->new_pointer_register();
-  __ move(LIR_OprFact::longConst(SIDE_METADATA_BASE_ADDRESS), base);
+  __ move(LIR_OprFact::longConst((jlong) barrier->log_bit_base), base);
   LIR_Address* meta_addr = new LIR_Address(base, offset, T_BYTE);
-  // intptr_t shift = (addr >> 3) & 0b111;
+  // intptr_t shift = (addr >> log_bit_shift) & log_bit_mask;
   LIR_Opr shift_long = gen->new_pointer_register();
   __ move(addr, shift_long);
-  __ shift_right(shift_long, 3, shift_long);
-  __ logical_and(shift_long, LIR_OprFact::longConst(0b111), shift_long);
+  __ shift_right(shift_long, (int) barrier->log_bit_shift, shift_long);
+  __ logical_and(shift_long, LIR_OprFact::longConst((jlong) barrier->log_bit_mask), shift_long);
   LIR_Opr shift_int = gen->new_register(T_INT);
   __ convert(Bytecodes::_l2i, shift_long, shift_int);
   LIR_Opr shift = LIRGenerator::shiftCountOpr();
@@ -147,6 +248,17 @@ void MMTkObjectBarrierSetC1::record_modified_node(LIRAccess& access, LIR_Opr src
   __ logical_and(result, LIR_OprFact::intConst(1), result);
   __ cmp(lir_cond_equal, result, LIR_OprFact::intConst(1));
   __ branch(lir_cond_equal, LP64_ONLY(T_LONG) NOT_LP64(T_INT), slow);
+}
+
+void MMTkObjectBarrierSetC1::record_modified_node(LIRAccess& access, LIR_Opr src, LIR_Opr slot, LIR_Opr new_val) {
+  LIRGenerator* gen = access.gen();
+  DecoratorSet decorators = access.decorators();
+  if ((decorators & IN_HEAP) == 0) return;
+  load_operands(gen, src, slot, new_val);
+  CodeStub* slow = new MMTkObjectBarrierStub(src, slot, new_val);
+
+#if MMTK_ENABLE_OBJECT_BARRIER_FASTPATH
+  branch_if_unlogged(gen, src, slow);
 #else
   __ jump(slow);
 #endif
@@ -167,6 +279,17 @@ const TypeFunc* record_modified_node_entry_Type() {
   return TypeFunc::make(domain, range);
 }
 
+Node* MMTkObjectBarrierSetC2::unlogged_bit(MMTkIdealKit& ideal, Node* src) {
+  const MMTkBarrierDescriptor* barrier = MMTkBarrierSet::descriptor;
+  Node* no_base = __ top();
+  Node* addr = __ CastPX(__ ctrl(), src);
+  Node* meta_addr = __ AddP(no_base, __ ConP((intptr_t) barrier->log_bit_base), __ URShiftX(addr, __ ConI((jint) barrier->log_bit_byte_shift)));
+  Node* byte = __ load(__ ctrl(), meta_addr, TypeInt::INT, T_BYTE, Compile::AliasIdxRaw);
+  Node* shift = __ URShiftX(addr, __ ConI((jint) barrier->log_bit_shift));
+  shift = __ AndI(__ ConvL2I(shift), __ ConI((jint) barrier->log_bit_mask));
+  return __ AndI(__ URShiftI(byte, shift), __ ConI(1));
+}
+
 void MMTkObjectBarrierSetC2::record_modified_node(GraphKit* kit, Node* src, Node* val) const {
   if (val != NULL && val->is_Con()) {
     const Type* t = val->bottom_type();
@@ -176,16 +299,10 @@ void MMTkObjectBarrierSetC2::record_modified_node(GraphKit* kit, Node* src, Node
   MMTkIdealKit ideal(kit, true);
 
 #if MMTK_ENABLE_OBJECT_BARRIER_FASTPATH
-  Node* no_base = __ top();
   float unlikely  = PROB_UNLIKELY(0.999);
 
   Node* zero  = __ ConI(0);
-  Node* addr = __ CastPX(__ ctrl(), src);
-  Node* meta_addr = __ AddP(no_base, __ ConP(SIDE_METADATA_BASE_ADDRESS), __ URShiftX(addr, __ ConI(6)));
-  Node* byte = __ load(__ ctrl(), meta_addr, TypeInt::INT, T_BYTE, Compile::AliasIdxRaw);
-  Node* shift = __ URShiftX(addr, __ ConI(3));
-  shift = __ AndI(__ ConvL2I(shift), __ ConI(7));
-  Node* result = __ AndI(__ URShiftI(byte, shift), __ ConI(1));
+  Node* result = unlogged_bit(ideal, src);
 
   __ if_then(result, BoolTest::ne, zero, unlikely); {
       const TypeFunc* tf = __ func_type(TypeOopPtr::BOTTOM);
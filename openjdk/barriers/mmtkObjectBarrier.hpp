@@ -14,21 +14,26 @@
 
 #define MMTK_ENABLE_OBJECT_BARRIER_FASTPATH true
 
-#define SIDE_METADATA_WORST_CASE_RATIO_LOG 1
-#define LOG_BYTES_IN_CHUNK 22
-#define CHUNK_MASK ((1L << LOG_BYTES_IN_CHUNK) - 1)
-
-const intptr_t SIDE_METADATA_BASE_ADDRESS = (intptr_t) GLOBAL_SIDE_METADATA_VM_BASE_ADDRESS;
-
 class MMTkObjectBarrierSetRuntime: public MMTkBarrierSetRuntime {
 public:
   static void record_modified_node_slow(void* src);
+  // The post barrier of the arraycopy stubs, which only know the slots.
+  static void array_copy_post_slow(void* dst_slots, size_t count);
+  // Whether the unlogged bit of the object is set.
+  static bool is_unlogged(void* obj);
+  // Clear the unlogged bit of the object. Returns false if it was clear already, i.e. the
+  // object is logged.
+  static bool log_object(void* obj);
+  // Set the log bit of the slot. Returns false if it was set already, i.e. the slot is logged.
+  static bool log_slot(void* slot);
 
   virtual bool is_slow_path_call(address call) {
     return call == CAST_FROM_FN_PTR(address, record_modified_node_slow);
   }
 
   virtual void record_modified_node(oop src);
+  virtual void array_copy_pre(oop src, oop dst, void* dst_slots, size_t count);
+  virtual void array_copy_post(oop src, oop dst, void* dst_slots, size_t count);
 };
 
 class MMTkObjectBarrierSetC1;
@@ -37,7 +42,12 @@ class MMTkObjectBarrierStub;
 class MMTkObjectBarrierSetAssembler: public MMTkBarrierSetAssembler {
   void oop_store_at(MacroAssembler* masm, DecoratorSet decorators, BasicType type, Address dst, Register val, Register tmp1, Register tmp2);
   void record_modified_node(MacroAssembler* masm, Register obj, Register tmp1, Register tmp2);
+protected:
+  // Jump to `logged` if the unlogged bit of obj is clear. Clobbers tmp, rscratch1 and rscratch2.
+  void jump_if_logged(MacroAssembler* masm, Register obj, Register tmp, Label& logged);
 public:
+  virtual void arraycopy_prologue(MacroAssembler* masm, DecoratorSet decorators, BasicType type, Register src, Register dst, Register count);
+  virtual void arraycopy_epilogue(MacroAssembler* masm, DecoratorSet decorators, BasicType type, Register src, Register dst, Register count);
   virtual void store_at(MacroAssembler* masm, DecoratorSet decorators, BasicType type, Address dst, Register val, Register tmp1, Register tmp2) {
     if (type == T_OBJECT || type == T_ARRAY) {
       oop_store_at(masm, decorators, type, dst, val, tmp1, tmp2);
@@ -114,7 +124,12 @@ public:
       return NULL;
     }
   };
-  void record_modified_node(LIRAccess& access, LIR_Opr src, LIR_Opr slot, LIR_Opr new_val);
+  virtual void record_modified_node(LIRAccess& access, LIR_Opr src, LIR_Opr slot, LIR_Opr new_val);
+protected:
+  // Put src, slot and new_val in registers.
+  void load_operands(LIRGenerator* gen, LIR_Opr& src, LIR_Opr& slot, LIR_Opr& new_val);
+  // Branch to slow if the unlogged bit of src is set.
+  void branch_if_unlogged(LIRGenerator* gen, LIR_Opr src, CodeStub* slow);
 public:
   CodeBlob* _write_barrier_c1_runtime_code_blob;
   virtual void store_at_resolved(LIRAccess& access, LIR_Opr value) {
@@ -154,6 +169,8 @@ public:
 class MMTkObjectBarrierSetC2: public MMTkBarrierSetC2 {
   void record_modified_node(GraphKit* kit, Node* node, Node* val) const;
 public:
+  // The unlogged bit of src, as an int.
+  static Node* unlogged_bit(MMTkIdealKit& ideal, Node* src);
   virtual Node* store_at_resolved(C2Access& access, C2AccessValue& val) const {
     Node* store = BarrierSetC2::store_at_resolved(access, val);
     if (access.is_oop()) record_modified_node(access.kit(), access.base(), val.node());
@@ -12,17 +12,24 @@ extern "C" {
 typedef void* MMTk_Mutator;
 typedef void* MMTk_TraceLocal;
 
-extern const uintptr_t GLOBAL_SIDE_METADATA_VM_BASE_ADDRESS;
+extern const size_t MMTK_MARK_COMPACT_HEADER_RESERVED_IN_BYTES;
 
 /**
  * Allocation
  */
 extern MMTk_Mutator bind_mutator(void *tls);
-extern void destroy_mutator(MMTk_Mutator mutator);
-extern void flush_mutator(MMTk_Mutator mutator);
 
-extern void* alloc(MMTk_Mutator mutator, size_t size,
-    size_t align, size_t offset, int allocator);
+// The functions that take a mutator, for the metadata layout it was bound with.
+// This type declaration needs to match MutatorFunctions in mmtk/src/api.rs
+typedef struct {
+    void (*destroy)(MMTk_Mutator mutator);
+    void (*flush)(MMTk_Mutator mutator);
+    void* (*alloc)(MMTk_Mutator mutator, size_t size, size_t align, size_t offset, int allocator);
+    void (*post_alloc)(MMTk_Mutator mutator, void* refer, size_t bytes, int allocator);
+    void (*record_modified_node)(MMTk_Mutator mutator, void* obj);
+} MMTkMutatorFunctions;
+
+extern const MMTkMutatorFunctions* mmtk_mutator_functions();
 
 extern void* alloc_slow_bump_monotone_immortal(MMTk_Mutator mutator, size_t size,
     size_t align, size_t offset);
@@ -31,12 +38,24 @@ extern void* alloc_slow_bump_monotone_copy(MMTk_Mutator mutator, size_t size,
 extern void* alloc_slow_largeobject(MMTk_Mutator mutator, size_t size,
     size_t align, size_t offset);
 
-extern void post_alloc(MMTk_Mutator mutator, void* refer,
-    int bytes, int allocator);
+extern void release_buffer(void** buffer, size_t len, size_t cap);
 
-extern void record_modified_node(MMTk_Mutator mutator, void* obj);
+typedef struct {
+    size_t reused;
+    size_t allocated;
+} EdgeBufferStats;
 
-extern void release_buffer(void** buffer, size_t len, size_t cap);
+extern EdgeBufferStats mmtk_take_edge_buffer_stats();
+
+typedef struct {
+    size_t packets;
+    size_t entries;
+    size_t trace_nanos;
+} ScanStats;
+
+extern ScanStats mmtk_take_scan_stats();
+
+extern void mmtk_invalidate_scan_descriptors();
 
 extern bool is_mapped_object(void* ref);
 extern bool is_mapped_address(void* addr);
@@ -51,6 +70,7 @@ struct AllocatorSelector {
 #define TAG_BUMP_POINTER    0
 #define TAG_LARGE_OBJECT    1
 #define TAG_MALLOC          2
+#define TAG_MARK_COMPACT    3
 
 extern AllocatorSelector get_allocator_mapping(int allocator);
 extern size_t get_max_non_los_default_alloc_bytes();
@@ -64,7 +84,6 @@ extern void* get_finalized_object();
 /**
  * Misc
  */
-extern char* mmtk_active_barrier();
 extern void enable_collection(void *tls);
 extern void gc_init(size_t heap_size);
 extern bool will_never_move(void* object);
@@ -88,6 +107,33 @@ typedef struct {
 
 typedef NewBuffer (*ProcessEdgesFn)(void** buf, size_t len, size_t cap);
 
+// This type declaration needs to match BarrierDescriptor in mmtk/src/barriers.rs
+typedef struct {
+    uint8_t kind;
+    bool header_metadata;
+    uintptr_t log_bit_base;
+    size_t log_bit_byte_shift;
+    size_t log_bit_shift;
+    size_t log_bit_mask;
+    uintptr_t slot_log_base;
+    size_t slot_log_byte_shift;
+    size_t slot_log_shift;
+    size_t forwarding_bits_shift;
+    uintptr_t forwarding_bits_mask;
+    NewBuffer (*record_modified_nodes)(MMTk_Mutator mutator, void** buf, size_t len, size_t cap);
+    NewBuffer (*record_modified_edges)(MMTk_Mutator mutator, void** buf, size_t len, size_t cap);
+    void (*array_copy_pre)(MMTk_Mutator mutator, void* src, void* dst, void* dst_slots, size_t count);
+    void (*array_copy_post)(MMTk_Mutator mutator, void* src, void* dst, void* dst_slots, size_t count);
+    void* (*load_reference_slow)(void* obj);
+} MMTkBarrierDescriptor;
+
+#define BARRIER_NO_BARRIER              0
+#define BARRIER_OBJECT_BARRIER          1
+#define BARRIER_FIELD_BARRIER           2
+#define BARRIER_LOAD_REFERENCE_BARRIER  3
+
+extern const MMTkBarrierDescriptor* mmtk_barrier_descriptor();
+
 /**
  * OpenJDK-specific
  */
@@ -125,11 +171,16 @@ typedef struct {
     void (*scan_system_dictionary_roots) (ProcessEdgesFn process_edges);
     void (*scan_code_cache_roots) (ProcessEdgesFn process_edges);
     void (*scan_string_table_roots) (ProcessEdgesFn process_edges);
-    void (*scan_class_loader_data_graph_roots) (ProcessEdgesFn process_edges);
+    void (*scan_class_loader_data_graph_roots) (ProcessEdgesFn process_edges, bool unload_classes);
     void (*scan_weak_processor_roots) (ProcessEdgesFn process_edges);
     void (*scan_vm_thread_roots) (ProcessEdgesFn process_edges);
     size_t (*number_of_mutators)();
     void (*schedule_finalizer)();
+    int (*klass_offset_in_mirror) ();
+    void (*enqueue_references) (void* head, void* tail);
+    void (*scan_cld) (ProcessEdgesFn process_edges, void* cld);
+    void* (*loader_data) (void* loader);
+    void (*process_weak_roots) (void* trace, bool (*is_alive)(void* object), void* (*forward)(void* trace, void* object));
 } OpenJDK_Upcalls;
 
 extern void openjdk_gc_init(OpenJDK_Upcalls *calls, size_t heap_size);
@@ -22,8 +22,9 @@
  *
  */
 
-#include <cstring>
+#include "logging/log.hpp"
 #include "runtime/interfaceSupport.inline.hpp"
+#include "runtime/java.hpp"
 #include "mmtkBarrierSet.hpp"
 #ifdef COMPILER1
 #include "mmtkBarrierSetC1.hpp"
@@ -34,14 +35,38 @@
 #include "mmtkBarrierSetAssembler_x86.hpp"
 #include "barriers/mmtkNoBarrier.hpp"
 #include "barriers/mmtkObjectBarrier.hpp"
+#include "barriers/mmtkFieldBarrier.hpp"
+#include "barriers/mmtkLoadReferenceBarrier.hpp"
+
+const MMTkBarrierDescriptor* MMTkBarrierSet::descriptor = NULL;
 
 MMTkBarrierBase* get_selected_barrier() {
     static MMTkBarrierBase* selected_barrier = NULL;
     if (selected_barrier) return selected_barrier;
-    const char* barrier = mmtk_active_barrier();
-    if (strcmp(barrier, "NoBarrier") == 0) selected_barrier = new MMTkNoBarrier();
-    else if (strcmp(barrier, "ObjectBarrier") == 0) selected_barrier = new MMTkObjectBarrier();
-    else guarantee(false, "Unimplemented");
+    MMTkBarrierSet::descriptor = mmtk_barrier_descriptor();
+    const char* barrier = NULL;
+    switch (MMTkBarrierSet::descriptor->kind) {
+    case BARRIER_NO_BARRIER:
+        barrier = "NoBarrier";
+        selected_barrier = new MMTkNoBarrier();
+        break;
+    case BARRIER_OBJECT_BARRIER:
+        barrier = "ObjectBarrier";
+        selected_barrier = new MMTkObjectBarrier();
+        break;
+    case BARRIER_FIELD_BARRIER:
+        barrier = "FieldBarrier";
+        selected_barrier = new MMTkFieldBarrier();
+        break;
+    case BARRIER_LOAD_REFERENCE_BARRIER:
+        barrier = "LoadReferenceBarrier";
+        selected_barrier = new MMTkLoadReferenceBarrier();
+        break;
+    default:
+        guarantee(false, "Unimplemented");
+    }
+    log_info(gc)("MMTk barrier: %s, %s metadata", barrier,
+                 MMTkBarrierSet::descriptor->header_metadata ? "header" : "side");
     return selected_barrier;
 }
 
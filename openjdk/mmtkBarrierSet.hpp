@@ -31,6 +31,7 @@
 #include "memory/memRegion.hpp"
 #include "oops/access.hpp"
 #include "oops/accessBackend.hpp"
+#include "oops/arrayOop.hpp"
 #include "oops/oopsHierarchy.hpp"
 #include "utilities/fakeRttiSupport.hpp"
 #include "mmtk.h"
@@ -41,6 +42,19 @@
 class MMTkBarrierSetRuntime: public CHeapObj<mtGC> {
 public:
   virtual void record_modified_node(oop object) {};
+  // A write into one slot of an object. Barriers that do not log slots log the whole object.
+  virtual void record_modified_edge(oop object, void* slot) {
+    record_modified_node(object);
+  }
+  // Around a copy of count elements into the reference array dst, from the slot dst_slots on.
+  // The arrays are NULL when the VM only knows the slots.
+  virtual void array_copy_pre(oop src, oop dst, void* dst_slots, size_t count) {}
+  virtual void array_copy_post(oop src, oop dst, void* dst_slots, size_t count) {}
+  // The reference a load from the heap or a root returns. Only barriers for plans that move
+  // objects while the mutators run need to resolve it.
+  virtual oop load_reference(oop object) {
+    return object;
+  }
   virtual bool is_slow_path_call(address call) {
     return false;
   }
@@ -81,6 +95,10 @@ protected:
   virtual void write_ref_array_work(MemRegion mr) ;
 
 public:
+  // The active barrier, and everything needed to emit it. It is read from MMTk when the barrier
+  // set is created. The fast paths must follow its metadata geometry.
+  static const MMTkBarrierDescriptor* descriptor;
+
   MMTkBarrierSet(MemRegion whole_heap);
 
   inline static MMTkBarrierSetRuntime* runtime() {
@@ -122,6 +140,20 @@ public:
   private:
     typedef BarrierSet::AccessBarrier<decorators, BarrierSetT> Raw;
   public:
+    template <typename T>
+    static oop oop_load_in_heap(T* addr) {
+      return runtime()->load_reference(Raw::oop_load_in_heap(addr));
+    }
+
+    static oop oop_load_in_heap_at(oop base, ptrdiff_t offset) {
+      return runtime()->load_reference(Raw::oop_load_in_heap_at(base, offset));
+    }
+
+    template <typename T>
+    static oop oop_load_not_in_heap(T* addr) {
+      return runtime()->load_reference(Raw::oop_load_not_in_heap(addr));
+    }
+
     template <typename T>
     static void oop_store_in_heap(T* addr, oop value) {
       UNREACHABLE();
@@ -129,7 +161,7 @@ public:
 
     static void oop_store_in_heap_at(oop base, ptrdiff_t offset, oop value) {
       Raw::oop_store_at(base, offset, value);
-      runtime()->record_modified_node(base);
+      runtime()->record_modified_edge(base, AccessInternal::field_addr(base, offset));
     }
 
     template <typename T>
@@ -140,7 +172,7 @@ public:
 
     static oop oop_atomic_cmpxchg_in_heap_at(oop new_value, oop base, ptrdiff_t offset, oop compare_value) {
       oop result = Raw::oop_atomic_cmpxchg_at(new_value, base, offset, compare_value);
-      runtime()->record_modified_node(base);
+      runtime()->record_modified_edge(base, AccessInternal::field_addr(base, offset));
       return result;
     }
 
@@ -152,7 +184,7 @@ public:
 
     static oop oop_atomic_xchg_in_heap_at(oop new_value, oop base, ptrdiff_t offset) {
       oop result = Raw::oop_atomic_xchg_at(new_value, base, offset);
-      runtime()->record_modified_node(base);
+      runtime()->record_modified_edge(base, AccessInternal::field_addr(base, offset));
       return result;
     }
 
@@ -160,10 +192,12 @@ public:
     static bool oop_arraycopy_in_heap(arrayOop src_obj, size_t src_offset_in_bytes, T* src_raw,
                                       arrayOop dst_obj, size_t dst_offset_in_bytes, T* dst_raw,
                                       size_t length) {
+      void* dst_slots = (void*) arrayOopDesc::obj_offset_to_raw(dst_obj, dst_offset_in_bytes, dst_raw);
+      runtime()->array_copy_pre((oop) src_obj, (oop) dst_obj, dst_slots, length);
       bool result = Raw::oop_arraycopy(src_obj, src_offset_in_bytes, src_raw,
                                 dst_obj, dst_offset_in_bytes, dst_raw,
                                 length);
-      runtime()->record_modified_node((oop) dst_obj);
+      runtime()->array_copy_post((oop) src_obj, (oop) dst_obj, dst_slots, length);
       return result;
     }
 
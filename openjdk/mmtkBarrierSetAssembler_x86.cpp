@@ -64,7 +64,9 @@ void MMTkBarrierSetAssembler::eden_allocate(MacroAssembler* masm, Register threa
     // We need to figure out which allocator we are using by querying MMTk.
     AllocatorSelector selector = get_allocator_mapping(allocator);
 
-    if (selector.tag == TAG_MALLOC || selector.tag == TAG_LARGE_OBJECT) {
+    // The mark compact allocator needs to reserve a header word in front of each object,
+    // which the bump pointer fastpath below does not know about. Use the slowpath for now.
+    if (selector.tag == TAG_MALLOC || selector.tag == TAG_LARGE_OBJECT || selector.tag == TAG_MARK_COMPACT) {
       __ jmp(slow_case);
       return;
     }
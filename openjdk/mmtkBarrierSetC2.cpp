@@ -131,7 +131,9 @@ void MMTkBarrierSetC2::expand_allocate(
 
   if (x->C->env()->dtrace_alloc_probes() || !MMTK_ENABLE_ALLOCATION_FASTPATH
     // Malloc allocator has no fastpath
-    || (selector.tag == TAG_MALLOC || selector.tag == TAG_LARGE_OBJECT)) {
+    || (selector.tag == TAG_MALLOC || selector.tag == TAG_LARGE_OBJECT)
+    // Mark compact allocator reserves an extra header in front of each object. Its fastpath is not implemented.
+    || selector.tag == TAG_MARK_COMPACT) {
     // Force slow-path allocation
     always_slow = true;
     initial_slow_test = NULL;
@@ -28,6 +28,7 @@
 #include "gc/shared/gcLocker.inline.hpp"
 #include "gc/shared/gcWhen.hpp"
 #include "logging/log.hpp"
+#include "memory/universe.hpp"
 #include "oops/oop.inline.hpp"
 #include "runtime/handles.inline.hpp"
 #include "runtime/java.hpp"
@@ -44,7 +45,12 @@
 #include "mmtkUpcalls.hpp"
 #include "services/management.hpp"
 #include "aot/aotLoader.hpp"
+#include "classfile/classLoaderData.hpp"
 #include "classfile/stringTable.hpp"
+#include "classfile/systemDictionary.hpp"
+#include "gc/shared/oopStorage.inline.hpp"
+#include "memory/metaspace.hpp"
+#include "prims/jvmtiExport.hpp"
 #include "runtime/atomic.hpp"
 /*
 needed support from rust
@@ -61,7 +67,7 @@ object iterator??!!
 
 MMTkHeap* MMTkHeap::_heap = NULL;
 
-MMTkHeap::MMTkHeap(MMTkCollectorPolicy* policy) : CollectedHeap(), _last_gc_time(0), _collector_policy(policy),  _num_root_scan_tasks(0), _n_workers(0), _gc_lock(new Monitor(Mutex::safepoint, "MMTkHeap::_gc_lock", true, Monitor::_safepoint_check_sometimes))
+MMTkHeap::MMTkHeap(MMTkCollectorPolicy* policy) : CollectedHeap(), _last_gc_time(0), _collector_policy(policy), _string_table_par_state(NULL), _n_workers(0), _gc_lock(new Monitor(Mutex::safepoint, "MMTkHeap::_gc_lock", true, Monitor::_safepoint_check_sometimes))
 // , _par_state_string(StringTable::weak_storage())
 {
    _heap = this;
@@ -69,7 +75,6 @@ MMTkHeap::MMTkHeap(MMTkCollectorPolicy* policy) : CollectedHeap(), _last_gc_time
 
 jint MMTkHeap::initialize() {
     assert(!UseTLAB , "should disable UseTLAB");
-    assert(!UseCompressedOops , "should disable CompressedOops");
     assert(!UseCompressedClassPointers , "should disable UseCompressedClassPointers");
     const size_t heap_size = collector_policy()->max_heap_byte_size();
    //  printf("policy max heap size %zu, min heap size %zu\n", heap_size, collector_policy()->min_heap_byte_size());
@@ -330,23 +335,68 @@ bool MMTkHeap::is_scavengable(oop obj) {return false;}
 // Heap verification
 void MMTkHeap::verify(VerifyOption option) {}
 
-template<int MAX_TASKS = 12>
-struct MMTkRootScanWorkScope {
-    int* _num_root_scan_tasks;
-    int _current_task_ordinal;
-    MMTkRootScanWorkScope(int* num_root_scan_tasks): _num_root_scan_tasks(num_root_scan_tasks), _current_task_ordinal(0) {
-      _current_task_ordinal = Atomic::add(1, _num_root_scan_tasks);
-      if (_current_task_ordinal == 1) {
-        nmethod::oops_do_marking_prologue();
-      }
+const char* MMTkHeap::root_scan_category_names[MMTkHeap::RootScanCategoryCount] = {
+    "Threads",
+    "Universe",
+    "JNI Handles",
+    "Object Synchronizer",
+    "Management",
+    "JVMTI Export",
+    "AOT Loader",
+    "System Dictionary",
+    "Code Cache",
+    "String Table",
+    "Class Loader Data Graph",
+    "Weak Processor",
+    "VM Thread",
+};
+
+// Times one root scanning packet.
+struct MMTkRootScanScope {
+    MMTkHeap::RootScanCategory _category;
+    jlong _start;
+    MMTkRootScanScope(MMTkHeap::RootScanCategory category): _category(category), _start(os::javaTimeNanos()) {}
+    ~MMTkRootScanScope() {
+      MMTkHeap::heap()->add_root_scan_time(_category, os::javaTimeNanos() - _start);
     }
-    ~MMTkRootScanWorkScope() {
-      if (_current_task_ordinal == MAX_TASKS) {
-        _current_task_ordinal = 0;
-        nmethod::oops_do_marking_epilogue();
+};
+
+void MMTkHeap::prepare_root_scanning() {
+   assert(SafepointSynchronize::is_at_safepoint(), "must be at a safepoint");
+   nmethod::oops_do_marking_prologue();
+   // The binding caches how to scan each klass. Redefinition may give a class a new layout.
+   if (JvmtiExport::has_redefined_a_class()) {
+      mmtk_invalidate_scan_descriptors();
+   }
+   _string_table_par_state = new OopStorage::ParState<false, false>(StringTable::weak_storage());
+   for (int i = 0; i < RootScanCategoryCount; i++) {
+      _root_scan_nanos[i] = 0;
+      _root_scan_packets[i] = 0;
+   }
+}
+
+void MMTkHeap::finish_root_scanning() {
+   assert(SafepointSynchronize::is_at_safepoint(), "must be at a safepoint");
+   delete _string_table_par_state;
+   _string_table_par_state = NULL;
+   nmethod::oops_do_marking_epilogue();
+   for (int i = 0; i < RootScanCategoryCount; i++) {
+      if (_root_scan_packets[i] > 0) {
+         log_debug(gc, phases)("Root scanning: %s: %.3fms in %d packets",
+                               root_scan_category_names[i], _root_scan_nanos[i] / (double) NANOSECS_PER_MILLISEC, _root_scan_packets[i]);
       }
    }
-};
+   EdgeBufferStats stats = mmtk_take_edge_buffer_stats();
+   log_debug(gc, phases)("Edge buffers: " SIZE_FORMAT " reused, " SIZE_FORMAT " allocated", stats.reused, stats.allocated);
+   ScanStats scan_stats = mmtk_take_scan_stats();
+   log_debug(gc, phases)("Object scanning: " SIZE_FORMAT " packets, " SIZE_FORMAT " entries, %.3fms tracing",
+                         scan_stats.packets, scan_stats.entries, scan_stats.trace_nanos / (double) NANOSECS_PER_MILLISEC);
+}
+
+void MMTkHeap::add_root_scan_time(RootScanCategory category, jlong nanos) {
+   Atomic::add(nanos, &_root_scan_nanos[category]);
+   Atomic::inc(&_root_scan_packets[category]);
+}
 
 void MMTkHeap::scan_static_roots(OopClosure& cl) {
 }
@@ -354,42 +404,42 @@ void MMTkHeap::scan_static_roots(OopClosure& cl) {
 
 void MMTkHeap::scan_universe_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
+   MMTkRootScanScope root_scan_scope(UniverseRoots);
    Universe::oops_do(&cl);
 }
 void MMTkHeap::scan_jni_handle_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
+   MMTkRootScanScope root_scan_scope(JNIHandleRoots);
    JNIHandles::oops_do(&cl);
 }
 void MMTkHeap::scan_object_synchronizer_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
+   MMTkRootScanScope root_scan_scope(ObjectSynchronizerRoots);
    ObjectSynchronizer::oops_do(&cl);
 }
 void MMTkHeap::scan_management_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
+   MMTkRootScanScope root_scan_scope(ManagementRoots);
    Management::oops_do(&cl);
 }
 void MMTkHeap::scan_jvmti_export_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
+   MMTkRootScanScope root_scan_scope(JvmtiExportRoots);
    JvmtiExport::oops_do(&cl);
 }
 void MMTkHeap::scan_aot_loader_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
+   MMTkRootScanScope root_scan_scope(AOTLoaderRoots);
    AOTLoader::oops_do(&cl);
 }
 void MMTkHeap::scan_system_dictionary_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
+   MMTkRootScanScope root_scan_scope(SystemDictionaryRoots);
    SystemDictionary::oops_do(&cl);
 }
 void MMTkHeap::scan_code_cache_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
+   MMTkRootScanScope root_scan_scope(CodeCacheRoots);
    CodeBlobToOopClosure cb_cl(&cl, true);
    {
       MutexLockerEx lock(CodeCache_lock, Mutex::_no_safepoint_check_flag);
@@ -397,31 +447,76 @@ void MMTkHeap::scan_code_cache_roots(OopClosure& cl) {
       CodeCache::blobs_do(&cb_cl);
    }
 }
+// Each packet claims blocks of the string table until there are none left. Like the weak
+// processor roots, only nursery GCs scan the string table.
 void MMTkHeap::scan_string_table_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
-   StringTable::oops_do(&cl);
+   MMTkRootScanScope root_scan_scope(StringTableRoots);
+   StringTable::possibly_parallel_oops_do(_string_table_par_state, &cl);
 }
-void MMTkHeap::scan_class_loader_data_graph_roots(OopClosure& cl) {
+// When classes may be unloaded, only the CLDs that are always alive are roots. The others are
+// claimed and scanned when the GC reaches them from a mirror, a class loader or an instance.
+// CLDs are claimed, so several packets can walk the graph together.
+void MMTkHeap::scan_class_loader_data_graph_roots(OopClosure& cl, bool unload_classes) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
-   CLDToOopClosure cld_cl(&cl, false);
-   ClassLoaderDataGraph::cld_do(&cld_cl);
+   MMTkRootScanScope root_scan_scope(ClassLoaderDataGraphRoots);
+   CLDToOopClosure cld_cl(&cl, true);
+   if (unload_classes && ClassUnloading) {
+      ClassLoaderDataGraph::always_strong_cld_do(&cld_cl);
+   } else {
+      ClassLoaderDataGraph::cld_do(&cld_cl);
+   }
 }
+// Only scanned in nursery GCs, which cannot tell whether a mature object is dead. Full-heap
+// GCs process these roots weakly in process_weak_roots() instead.
 void MMTkHeap::scan_weak_processor_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
-   WeakProcessor::oops_do(&cl); // (really needed???)
+   MMTkRootScanScope root_scan_scope(WeakProcessorRoots);
+   WeakProcessor::oops_do(&cl);
+}
+
+// Called after the closure of a full-heap GC, like the weak processing in
+// MarkSweep::mark_sweep_phase1(). Dead weak JNI handles and interned strings are cleared, the
+// others are updated to the new location of their objects.
+void MMTkHeap::process_weak_roots(BoolObjectClosure& is_alive, OopClosure& forward) {
+   ResourceMark rm;
+   {
+      MMTkRootScanScope root_scan_scope(WeakProcessorRoots);
+      // This also clears the holders of dead CLDs, which are in the VM weak storage.
+      WeakProcessor::weak_oops_do(&is_alive, &forward);
+   }
+   {
+      MMTkRootScanScope root_scan_scope(StringTableRoots);
+      int processed = 0;
+      int removed = 0;
+      StringTable::unlink_or_oops_do(&is_alive, &forward, &processed, &removed);
+      log_debug(gc, stringtable)("Cleaned string table, strings: %d processed, %d removed", processed, removed);
+   }
+   unload_classes(is_alive);
+}
+
+// The class unloading part of MarkSweep::mark_sweep_phase1(). The holders of dead CLDs must
+// already be cleared.
+void MMTkHeap::unload_classes(BoolObjectClosure& is_alive) {
+   if (!ClassUnloading) return;
+   bool purged_class = SystemDictionary::do_unloading(NULL);
+   CodeCache::do_unloading(&is_alive, purged_class);
+   Klass::clean_weak_klass_links(purged_class);
+   // Free the metaspace of the unloaded classes. New klasses may then reuse their addresses.
+   ClassLoaderDataGraph::purge();
+   MetaspaceGC::compute_new_size();
+   if (purged_class) {
+      mmtk_invalidate_scan_descriptors();
+   }
 }
 void MMTkHeap::scan_vm_thread_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
+   MMTkRootScanScope root_scan_scope(VMThreadRoots);
    VMThread::vm_thread()->oops_do(&cl, NULL);
 }
 
 void MMTkHeap::scan_global_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
 
    CodeBlobToOopClosure cb_cl(&cl, true);
    CLDToOopClosure cld_cl(&cl, false);
@@ -449,7 +544,7 @@ void MMTkHeap::scan_global_roots(OopClosure& cl) {
 
 void MMTkHeap::scan_thread_roots(OopClosure& cl) {
    ResourceMark rm;
-   MMTkRootScanWorkScope<> root_scan_work(&_num_root_scan_tasks);
+   MMTkRootScanScope root_scan_scope(ThreadRoots);
    Threads::possibly_parallel_oops_do(false, &cl, NULL);
 }
 
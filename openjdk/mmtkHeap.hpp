@@ -56,7 +56,31 @@ class MMTkHeap : public CollectedHeap {
     size_t _n_workers;
     Monitor* _gc_lock;
     ContiguousSpace* _space;
-    int _num_root_scan_tasks;
+public:
+  // The root sets timed during a GC. Keep root_scan_category_names in sync.
+  enum RootScanCategory {
+    ThreadRoots,
+    UniverseRoots,
+    JNIHandleRoots,
+    ObjectSynchronizerRoots,
+    ManagementRoots,
+    JvmtiExportRoots,
+    AOTLoaderRoots,
+    SystemDictionaryRoots,
+    CodeCacheRoots,
+    StringTableRoots,
+    ClassLoaderDataGraphRoots,
+    WeakProcessorRoots,
+    VMThreadRoots,
+    RootScanCategoryCount
+  };
+private:
+  static const char* root_scan_category_names[RootScanCategoryCount];
+  // Time spent scanning each root set in the current GC, summed over the workers.
+  volatile jlong _root_scan_nanos[RootScanCategoryCount];
+  volatile jint _root_scan_packets[RootScanCategoryCount];
+  // Shared by the workers that scan the string table in parallel.
+  OopStorage::ParState<false, false>* _string_table_par_state;
 public:
 
   MMTkHeap(MMTkCollectorPolicy* policy);
@@ -196,8 +220,15 @@ public:
   void scan_system_dictionary_roots(OopClosure& cl);
   void scan_code_cache_roots(OopClosure& cl);
   void scan_string_table_roots(OopClosure& cl);
-  void scan_class_loader_data_graph_roots(OopClosure& cl);
+  void scan_class_loader_data_graph_roots(OopClosure& cl, bool unload_classes);
   void scan_weak_processor_roots(OopClosure& cl);
+  void process_weak_roots(BoolObjectClosure& is_alive, OopClosure& forward);
+  void unload_classes(BoolObjectClosure& is_alive);
+
+  // Called at the start and the end of each GC, inside the safepoint.
+  void prepare_root_scanning();
+  void finish_root_scanning();
+  void add_root_scan_time(RootScanCategory category, jlong nanos);
   void scan_vm_thread_roots(OopClosure& cl);
 
   virtual void report_java_thread_yield(JavaThread* thread);
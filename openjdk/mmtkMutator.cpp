@@ -1,11 +1,24 @@
 
 #include "mmtk.h"
 #include "mmtkMutator.hpp"
+#include "mmtkBarrierSet.hpp"
+#include <string.h>
 
 size_t MMTkMutatorContext::max_non_los_default_alloc_bytes = 0;
 
 MMTkMutatorContext MMTkMutatorContext::bind(::Thread* current) {
-    return *((MMTkMutatorContext*) ::bind_mutator((void*) current));
+    MMTkMutatorContext context;
+    // The mutator from MMTk core ends where the binding fields start.
+    memcpy((void*) &context, ::bind_mutator((void*) current), offset_of(MMTkMutatorContext, functions));
+    context.functions = ::mmtk_mutator_functions();
+    // The buffers are taken on the first logged object or slot.
+    context.modified_nodes.buf = NULL;
+    context.modified_nodes.cursor = 0;
+    context.modified_nodes.cap = 0;
+    context.modified_edges.buf = NULL;
+    context.modified_edges.cursor = 0;
+    context.modified_edges.cap = 0;
+    return context;
 }
 
 HeapWord* MMTkMutatorContext::alloc(size_t bytes, Allocator allocator) {
@@ -17,13 +30,40 @@ HeapWord* MMTkMutatorContext::alloc(size_t bytes, Allocator allocator) {
     }
 
     // FIXME: Proper use of slow-path api
-    HeapWord* o = (HeapWord*) ::alloc((MMTk_Mutator) this, bytes, HeapWordSize, 0, allocator);
+    HeapWord* o = (HeapWord*) functions->alloc((MMTk_Mutator) this, bytes, HeapWordSize, 0, allocator);
     // Post allococation. Currently we are only calling post_alloc in slowpath here.
     // TODO: We also need to call them in the fastpath.
-    ::post_alloc((MMTk_Mutator) this, o, bytes, allocator);
+    functions->post_alloc((MMTk_Mutator) this, o, bytes, allocator);
     return o;
 }
 
+void MMTkMutatorContext::flush_modified_nodes() {
+    NewBuffer buf = MMTkBarrierSet::descriptor->record_modified_nodes((MMTk_Mutator) this, modified_nodes.buf, modified_nodes.cursor, modified_nodes.cap);
+    modified_nodes.buf = buf.buf;
+    modified_nodes.cap = buf.cap;
+    modified_nodes.cursor = 0;
+}
+
+void MMTkMutatorContext::flush_modified_edges() {
+    NewBuffer buf = MMTkBarrierSet::descriptor->record_modified_edges((MMTk_Mutator) this, modified_edges.buf, modified_edges.cursor, modified_edges.cap);
+    modified_edges.buf = buf.buf;
+    modified_edges.cap = buf.cap;
+    modified_edges.cursor = 0;
+}
+
 void MMTkMutatorContext::flush() {
-    ::flush_mutator((MMTk_Mutator) this);
+    // The thread may exit, so give the buffers back.
+    if (modified_nodes.buf != NULL) {
+        if (modified_nodes.cursor > 0) flush_modified_nodes();
+        ::release_buffer(modified_nodes.buf, 0, modified_nodes.cap);
+        modified_nodes.buf = NULL;
+        modified_nodes.cap = 0;
+    }
+    if (modified_edges.buf != NULL) {
+        if (modified_edges.cursor > 0) flush_modified_edges();
+        ::release_buffer(modified_edges.buf, 0, modified_edges.cap);
+        modified_edges.buf = NULL;
+        modified_edges.cap = 0;
+    }
+    functions->flush((MMTk_Mutator) this);
 }
\ No newline at end of file
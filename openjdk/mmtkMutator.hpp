@@ -22,6 +22,7 @@ struct RustDynPtr {
 const int MAX_BUMP_ALLOCATORS = 5;
 const int MAX_LARGE_OBJECT_ALLOCATORS = 1;
 const int MAX_MALLOC_ALLOCATORS = 1;
+const int MAX_MARK_COMPACT_ALLOCATORS = 1;
 
 // The following types should have the same layout as the types with the same name in MMTk core (Rust)
 
@@ -45,10 +46,16 @@ struct MallocAllocator {
     RustDynPtr plan;
 };
 
+// A bump allocator that reserves MMTK_MARK_COMPACT_HEADER_RESERVED_IN_BYTES in front of each object.
+struct MarkCompactAllocator {
+    BumpAllocator bump_allocator;
+};
+
 struct Allocators {
     BumpAllocator bump_pointer[MAX_BUMP_ALLOCATORS];
     LargeObjectAllocator large_object[MAX_LARGE_OBJECT_ALLOCATORS];
     MallocAllocator malloc[MAX_MALLOC_ALLOCATORS];
+    MarkCompactAllocator markcompact[MAX_MARK_COMPACT_ALLOCATORS];
 };
 
 struct MutatorConfig {
@@ -58,6 +65,15 @@ struct MutatorConfig {
     RustDynPtr release_func;
 };
 
+// The objects or slots logged by the barrier of a thread, handed to MMTk in batches with
+// the record_modified_nodes or record_modified_edges slow path of the barrier descriptor.
+// It is owned like the edge buffers of root scanning.
+struct BarrierBuffer {
+    void** buf;
+    size_t cursor;
+    size_t cap;
+};
+
 struct MMTkMutatorContext {
     Allocators allocators;
     RustDynPtr barrier;
@@ -65,8 +81,33 @@ struct MMTkMutatorContext {
     RustDynPtr plan;
     MutatorConfig config;
 
+    // The fields above mirror the mutator in MMTk core. The ones below belong to the binding.
+    const MMTkMutatorFunctions* functions;
+    BarrierBuffer modified_nodes;
+    BarrierBuffer modified_edges;
+
     HeapWord* alloc(size_t bytes, Allocator allocator = AllocatorDefault);
 
+    // The barrier clears the unlogged bit of the object first, so an object is logged once
+    // between two GCs.
+    inline void record_modified_node(void* obj) {
+        if (modified_nodes.cursor >= modified_nodes.cap) flush_modified_nodes();
+        modified_nodes.buf[modified_nodes.cursor++] = obj;
+    }
+
+    // Hand the logged objects to MMTk. It is called when the buffer is full, from flush(), and
+    // for every thread when the mutators are stopped for a GC.
+    void flush_modified_nodes();
+
+    // Likewise, the barrier sets the log bit of the slot first.
+    inline void record_modified_edge(void* slot) {
+        if (modified_edges.cursor >= modified_edges.cap) flush_modified_edges();
+        modified_edges.buf[modified_edges.cursor++] = slot;
+    }
+
+    // Hand the logged slots to MMTk, like flush_modified_nodes.
+    void flush_modified_edges();
+
     void flush();
 
     static MMTkMutatorContext bind(::Thread* current);
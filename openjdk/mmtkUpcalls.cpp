@@ -41,20 +41,42 @@
 #include "runtime/thread.hpp"
 #include "runtime/threadSMR.hpp"
 #include "memory/resourceArea.hpp"
+#include "memory/universe.hpp"
+#include "classfile/classLoaderData.hpp"
+#include "classfile/javaClasses.hpp"
 #include "classfile/stringTable.hpp"
 #include "code/nmethod.hpp"
 #include "memory/iterator.inline.hpp"
+#include "oops/access.inline.hpp"
 
 static bool gcInProgress = false;
 
+// Hands the objects and slots logged by the barriers of a thread to MMTk.
+class MMTkFlushBarrierBuffersClosure: public ThreadClosure {
+public:
+    virtual void do_thread(Thread* thread) {
+        MMTkMutatorContext* mutator = &thread->third_party_heap_mutator;
+        if (mutator->modified_nodes.cursor > 0) mutator->flush_modified_nodes();
+        if (mutator->modified_edges.cursor > 0) mutator->flush_modified_edges();
+    }
+};
+
 static void mmtk_stop_all_mutators(void *tls, void (*create_stack_scan_work)(void* mutator)) {
     gcInProgress = true;
     MMTkHeap::_create_stack_scan_work = create_stack_scan_work;
     SafepointSynchronize::begin();
+    // MMTk has to see what the barriers logged before the GC. Every thread has a mutator, and
+    // the VM thread and the other non-Java threads may write into the heap too.
+    MMTkFlushBarrierBuffersClosure flush_cl;
+    Threads::threads_do(&flush_cl);
+    // CLDs are claimed when the GC scans them.
+    ClassLoaderDataGraph::clear_claimed_marks();
+    MMTkHeap::heap()->prepare_root_scanning();
 }
 
 static void mmtk_resume_mutators(void *tls) {
     MMTkHeap::_create_stack_scan_work = NULL;
+    MMTkHeap::heap()->finish_root_scanning();
     SafepointSynchronize::end();
     MMTkHeap::heap()->gc_lock()->lock_without_safepoint_check();
     gcInProgress = false;
@@ -158,9 +180,13 @@ static void mmtk_scan_thread_roots(ProcessEdgesFn process_edges) {
 
 static void mmtk_scan_thread_root(ProcessEdgesFn process_edges, void* tls) {
     ResourceMark rm;
+    jlong start = os::javaTimeNanos();
     JavaThread* thread = (JavaThread*) tls;
-    MMTkRootsClosure2 cl(process_edges);
-    thread->oops_do(&cl, NULL);
+    {
+        MMTkRootsClosure2 cl(process_edges);
+        thread->oops_do(&cl, NULL);
+    }
+    MMTkHeap::heap()->add_root_scan_time(MMTkHeap::ThreadRoots, os::javaTimeNanos() - start);
 }
 
 static void mmtk_scan_object(void* trace, void* object, void* tls) {
@@ -215,7 +241,13 @@ static size_t compute_klass_mem_layout_checksum() {
     ^ sizeof(InstanceMirrorKlass)
     ^ sizeof(InstanceClassLoaderKlass)
     ^ sizeof(TypeArrayKlass)
-    ^ sizeof(ObjArrayKlass);
+    ^ sizeof(ObjArrayKlass)
+    ^ sizeof(Symbol)
+    ^ sizeof(oopDesc);
+}
+
+static int klass_offset_in_mirror() {
+    return java_lang_Class::klass_offset_in_bytes();
 }
 
 static int referent_offset() {
@@ -244,7 +276,7 @@ static void mmtk_scan_aot_loader_roots(ProcessEdgesFn process_edges) { MMTkRoots
 static void mmtk_scan_system_dictionary_roots(ProcessEdgesFn process_edges) { MMTkRootsClosure2 cl(process_edges); MMTkHeap::heap()->scan_system_dictionary_roots(cl); }
 static void mmtk_scan_code_cache_roots(ProcessEdgesFn process_edges) { MMTkRootsClosure2 cl(process_edges); MMTkHeap::heap()->scan_code_cache_roots(cl); }
 static void mmtk_scan_string_table_roots(ProcessEdgesFn process_edges) { MMTkRootsClosure2 cl(process_edges); MMTkHeap::heap()->scan_string_table_roots(cl); }
-static void mmtk_scan_class_loader_data_graph_roots(ProcessEdgesFn process_edges) { MMTkRootsClosure2 cl(process_edges); MMTkHeap::heap()->scan_class_loader_data_graph_roots(cl); }
+static void mmtk_scan_class_loader_data_graph_roots(ProcessEdgesFn process_edges, bool unload_classes) { MMTkRootsClosure2 cl(process_edges); MMTkHeap::heap()->scan_class_loader_data_graph_roots(cl, unload_classes); }
 static void mmtk_scan_weak_processor_roots(ProcessEdgesFn process_edges) { MMTkRootsClosure2 cl(process_edges); MMTkHeap::heap()->scan_weak_processor_roots(cl); }
 static void mmtk_scan_vm_thread_roots(ProcessEdgesFn process_edges) { MMTkRootsClosure2 cl(process_edges); MMTkHeap::heap()->scan_vm_thread_roots(cl); }
 
@@ -252,6 +284,56 @@ static size_t mmtk_number_of_mutators() {
     return Threads::number_of_threads();
 }
 
+// Prepend the list of cleared references (linked through Reference.discovered) to the
+// pending list, and wake up the ReferenceHandler thread.
+static void mmtk_enqueue_references(void* head, void* tail) {
+    MutexLockerEx ml(Heap_lock, Mutex::_no_safepoint_check_flag);
+    oop old = Universe::swap_reference_pending_list((oop) head);
+    java_lang_ref_Reference::set_discovered_raw((oop) tail, old);
+    Heap_lock->notify_all();
+}
+
+// Visit the oops of a CLD reached by the GC, unless it is already claimed in this GC.
+static void mmtk_scan_cld(ProcessEdgesFn process_edges, void* cld) {
+    MMTkRootsClosure2 cl(process_edges);
+    ((ClassLoaderData*) cld)->oops_do(&cl, true);
+}
+
+static void* mmtk_loader_data(void* loader) {
+    return (void*) java_lang_ClassLoader::loader_data((oop) loader);
+}
+
+class MMTkIsAliveClosure : public BoolObjectClosure {
+    bool (*_is_alive)(void* object);
+public:
+    MMTkIsAliveClosure(bool (*is_alive)(void* object)): _is_alive(is_alive) {}
+    virtual bool do_object_b(oop obj) { return _is_alive((void*) obj); }
+};
+
+// Updates a slot that points to a live object to the object's new location.
+class MMTkForwardClosure : public OopClosure {
+    void* _trace;
+    void* (*_forward)(void* trace, void* object);
+
+    template <class T>
+    void do_oop_work(T* p) {
+        oop obj = RawAccess<>::oop_load(p);
+        if (obj != NULL) {
+            RawAccess<IS_NOT_NULL>::oop_store(p, (oop) _forward(_trace, (void*) obj));
+        }
+    }
+public:
+    MMTkForwardClosure(void* trace, void* (*forward)(void* trace, void* object)): _trace(trace), _forward(forward) {}
+    virtual void do_oop(oop* p)       { do_oop_work(p); }
+    virtual void do_oop(narrowOop* p) { do_oop_work(p); }
+};
+
+static void mmtk_process_weak_roots(void* trace, bool (*is_alive)(void* object), void* (*forward)(void* trace, void* object)) {
+    MMTkIsAliveClosure is_alive_cl(is_alive);
+    MMTkForwardClosure forward_cl(trace, forward);
+    MMTkHeap::heap()->process_weak_roots(is_alive_cl, forward_cl);
+}
+
 OpenJDK_Upcalls mmtk_upcalls = {
     mmtk_stop_all_mutators,
     mmtk_resume_mutators,
@@ -291,4 +373,9 @@ OpenJDK_Upcalls mmtk_upcalls = {
     mmtk_scan_vm_thread_roots,
     mmtk_number_of_mutators,
     mmtk_schedule_finalizer,
+    klass_offset_in_mirror,
+    mmtk_enqueue_references,
+    mmtk_scan_cld,
+    mmtk_loader_data,
+    mmtk_process_weak_roots,
 };
\ No newline at end of file
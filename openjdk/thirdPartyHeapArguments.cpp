@@ -43,7 +43,16 @@ void ThirdPartyHeapArguments::initialize() {
   GCArguments::initialize();
   assert(UseThirdPartyHeap , "Error, should UseThirdPartyHeap");
   FLAG_SET_DEFAULT(UseTLAB, false);
+  // mmtk-core maps its spaces at fixed ranges that span far more than compressed oops can
+  // address, whatever the heap size.
+  if (UseCompressedOops && FLAG_IS_CMDLINE(UseCompressedOops)) {
+    warning("UseCompressedOops is not supported by MMTk");
+  }
   FLAG_SET_DEFAULT(UseCompressedOops, false);
+  // Compressed class pointers need compressed oops.
+  if (UseCompressedClassPointers && FLAG_IS_CMDLINE(UseCompressedClassPointers)) {
+    warning("UseCompressedClassPointers requires UseCompressedOops");
+  }
   FLAG_SET_DEFAULT(UseCompressedClassPointers, false);
 }
 